@@ -1,6 +1,7 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
 use aoc2020::solutions::build_runner;
+use aoc2020::solutions::day23;
 
 fn criterion_benchmark(c: &mut Criterion) {
     let runner = build_runner();
@@ -9,5 +10,16 @@ fn criterion_benchmark(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Crab cups with a million cups and ten million moves, on the example labelling so that it doesn't
+/// depend on having puzzle input.
+fn crab_cups_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("day23");
+    group.sample_size(10);
+    group.bench_function("part2_example1", |b| {
+        b.iter(|| day23::part2_impl(&[3, 8, 9, 1, 2, 5, 4, 6, 7]))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark, crab_cups_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,39 @@
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use aoc2020::solutions::day07;
+
+/// Inspect the rules in a day 7 input file, e.g.
+/// `bag_rules data/day07_input.txt dot | dot -Tsvg > bags.svg`,
+/// `bag_rules data/day07_input.txt path "light red" "shiny gold"` or
+/// `bag_rules data/day07_input.txt cycle`.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let usage = || -> ! {
+        eprintln!(
+            "usage: {} <input> dot | path <outer> <inner> | cycle",
+            args[0]
+        );
+        process::exit(2);
+    };
+    if args.len() < 3 {
+        usage();
+    }
+    let input_path = PathBuf::from(&args[1]);
+    let result = match (args[2].as_str(), &args[3..]) {
+        ("dot", []) => day07::export_dot(input_path),
+        ("path", [outer, inner]) => {
+            day07::containment_path(input_path, outer, inner).map(|path| path + "\n")
+        }
+        ("cycle", []) => day07::describe_cycle(input_path).map(|cycle| cycle + "\n"),
+        _ => usage(),
+    };
+    match result {
+        Ok(output) => print!("{}", output),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            process::exit(1);
+        }
+    }
+}
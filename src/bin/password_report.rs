@@ -0,0 +1,22 @@
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use aoc2020::solutions::day02;
+
+/// Report which passwords in a day 2 input file fail which policies, e.g.
+/// `password_report data/day02_input.txt count position entropy:20`.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <input> <policy>...", args[0]);
+        process::exit(2);
+    }
+    match day02::report(PathBuf::from(&args[1]), &args[2..]) {
+        Ok(report) => print!("{}", report),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use aoc2020::solutions::day12;
+
+/// Plot the route through a day 12 navigation file, e.g.
+/// `plot_navigation data/day12_input.txt waypoint svg > route.svg` or
+/// `plot_navigation data/day12_input.txt direct ascii 80x40`.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let usage = || -> ! {
+        eprintln!(
+            "usage: {} <input> direct|waypoint svg|ascii [<width>x<height>]",
+            args[0]
+        );
+        process::exit(2);
+    };
+    if args.len() < 4 {
+        usage();
+    }
+    let input_path = PathBuf::from(&args[1]);
+    let via_waypoint = match args[2].as_str() {
+        "direct" => false,
+        "waypoint" => true,
+        _ => usage(),
+    };
+    let result = match args[3].as_str() {
+        "svg" => day12::plot_svg(input_path, via_waypoint),
+        "ascii" => {
+            let raw_size = args.get(4).map(String::as_str).unwrap_or("80x40");
+            let (width, height) = match raw_size.split_once('x') {
+                Some((w, h)) => match (w.parse(), h.parse()) {
+                    (Ok(w), Ok(h)) => (w, h),
+                    _ => usage(),
+                },
+                None => usage(),
+            };
+            day12::plot_ascii(input_path, via_waypoint, width, height)
+        }
+        _ => usage(),
+    };
+    match result {
+        Ok(plot) => print!("{}", plot),
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -3,7 +3,9 @@ pub mod util;
 
 pub mod error;
 pub use error::{Error, Result};
+pub mod modular;
 pub mod runner;
 pub use runner::Runner;
 pub mod solutions;
 pub mod vector;
+pub mod vm;
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+/// Calculate `(a * b) mod m` without overflowing.
+pub fn mod_mul(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+/// Calculate `base.pow(exp) mod m` by repeated squaring.
+pub fn mod_pow(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    let mut result = 1 % m;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mod_mul(result, base, m);
+        }
+        base = mod_mul(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Find `x` such that `(a * x) mod m == 1`, if it exists (i.e. if `a` and `m` are coprime).
+pub fn mod_inverse(a: u64, m: u64) -> Option<u64> {
+    let (mut old_r, mut r) = (a as i128 % m as i128, m as i128);
+    let (mut old_s, mut s) = (1_i128, 0_i128);
+    while r != 0 {
+        let q = old_r / r;
+        let (new_r, new_s) = (old_r - q * r, old_s - q * s);
+        old_r = r;
+        r = new_r;
+        old_s = s;
+        s = new_s;
+    }
+    if old_r == 1 {
+        Some(old_s.rem_euclid(m as i128) as u64)
+    } else {
+        None
+    }
+}
+
+/// Find the smallest `x` such that `base.pow(x) mod m == target`, if there is one.
+///
+/// Uses the baby-step giant-step algorithm, which takes O(sqrt(m)) time and space instead of the
+/// O(m) of trying every exponent in turn. `base` must be coprime with `m`.
+pub fn discrete_log(base: u64, target: u64, m: u64) -> Option<u64> {
+    let target = target % m;
+    let n = (m as f64).sqrt().ceil() as u64;
+
+    // Baby steps: base^j for j in 0..n, keeping the smallest j for each value
+    let mut baby_steps: HashMap<u64, u64> = HashMap::with_capacity(n as usize);
+    let mut value = 1 % m;
+    for j in 0..n {
+        baby_steps.entry(value).or_insert(j);
+        value = mod_mul(value, base, m);
+    }
+
+    // Giant steps: target * base^(-n * i) for i in 0..n, looking for a match in the baby steps
+    let factor = mod_pow(mod_inverse(base, m)?, n, m);
+    let mut gamma = target;
+    for i in 0..n {
+        if let Some(j) = baby_steps.get(&gamma) {
+            return Some(i * n + j);
+        }
+        gamma = mod_mul(gamma, factor, m);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mod_pow() {
+        assert_eq!(mod_pow(7, 8, 20201227), 5764801);
+        assert_eq!(mod_pow(17807724, 8, 20201227), 14897079);
+        assert_eq!(mod_pow(2, 0, 1), 0);
+        assert_eq!(mod_pow(u64::MAX, 2, u64::MAX - 1), 1);
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(10, 17), Some(12));
+        assert_eq!(mod_inverse(6, 9), None);
+    }
+
+    #[test]
+    fn test_discrete_log() {
+        assert_eq!(discrete_log(7, 5764801, 20201227), Some(8));
+        assert_eq!(discrete_log(7, 17807724, 20201227), Some(11));
+        assert_eq!(discrete_log(7, 1, 20201227), Some(0));
+        // 2 only generates {1, 2, 4} modulo 7
+        assert_eq!(discrete_log(2, 3, 7), None);
+    }
+}
@@ -1,5 +1,8 @@
+use std::collections::HashMap;
 use std::str::FromStr;
 
+use regex::Regex;
+
 use super::prelude::*;
 use crate::util;
 
@@ -10,24 +13,6 @@ struct Policy {
     char: char,
 }
 
-impl Policy {
-    fn valid_by_char_count(&self, password: &str) -> bool {
-        let mut count: u8 = 0;
-        for c in password.chars() {
-            if c == self.char {
-                count += 1;
-            }
-        }
-        count >= self.min && count <= self.max
-    }
-
-    fn valid_by_char_position(&self, password: &str) -> bool {
-        let match_min = password.as_bytes()[(self.min - 1) as usize] as char == self.char;
-        let match_max = password.as_bytes()[(self.max - 1) as usize] as char == self.char;
-        (match_min || match_max) && !(match_min && match_max)
-    }
-}
-
 impl FromStr for Policy {
     type Err = crate::Error;
 
@@ -59,28 +44,183 @@ impl FromStr for Example {
     }
 }
 
+/// A way of deciding whether a password is valid.
+///
+/// The puzzle's policies interpret the `Policy` attached to each password, while others apply the
+/// same rule to every password.
+trait PasswordPolicy {
+    /// Name that selects this policy in `parse_policy()`.
+    fn name(&self) -> String;
+    fn is_valid(&self, example: &Example) -> bool;
+}
+
+/// Part 1: "The password policy indicates the lowest and highest number of times a given letter
+/// must appear for the password to be valid."
+struct CharCount;
+
+impl PasswordPolicy for CharCount {
+    fn name(&self) -> String {
+        "count".into()
+    }
+
+    fn is_valid(&self, example: &Example) -> bool {
+        let policy = &example.policy;
+        let count = example
+            .password
+            .chars()
+            .filter(|&c| c == policy.char)
+            .count();
+        (policy.min as usize..=policy.max as usize).contains(&count)
+    }
+}
+
+/// Part 2: "Exactly one of these positions must contain the given letter", where positions count
+/// from 1.
+struct CharPosition;
+
+impl PasswordPolicy for CharPosition {
+    fn name(&self) -> String {
+        "position".into()
+    }
+
+    fn is_valid(&self, example: &Example) -> bool {
+        let policy = &example.policy;
+        let matches = |pos: u8| {
+            (pos as usize)
+                .checked_sub(1)
+                .and_then(|i| example.password.chars().nth(i))
+                == Some(policy.char)
+        };
+        matches(policy.min) != matches(policy.max)
+    }
+}
+
+/// Password must match a regular expression.
+struct MatchesRegex(Regex);
+
+impl PasswordPolicy for MatchesRegex {
+    fn name(&self) -> String {
+        format!("regex:{}", self.0)
+    }
+
+    fn is_valid(&self, example: &Example) -> bool {
+        self.0.is_match(&example.password)
+    }
+}
+
+/// Password must not contain any of a list of substrings.
+struct ForbiddenSubstrings(Vec<String>);
+
+impl PasswordPolicy for ForbiddenSubstrings {
+    fn name(&self) -> String {
+        format!("forbid:{}", self.0.join(","))
+    }
+
+    fn is_valid(&self, example: &Example) -> bool {
+        !self.0.iter().any(|s| example.password.contains(s.as_str()))
+    }
+}
+
+/// Password must have at least this much Shannon entropy in total, in bits, based on the frequency
+/// of characters within the password.
+struct MinEntropy(f64);
+
+impl MinEntropy {
+    fn entropy(password: &str) -> f64 {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        for c in password.chars() {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        let len = password.chars().count() as f64;
+        counts
+            .values()
+            .map(|&count| {
+                let count = count as f64;
+                -count * (count / len).log2()
+            })
+            .sum()
+    }
+}
+
+impl PasswordPolicy for MinEntropy {
+    fn name(&self) -> String {
+        format!("entropy:{}", self.0)
+    }
+
+    fn is_valid(&self, example: &Example) -> bool {
+        Self::entropy(&example.password) >= self.0
+    }
+}
+
+/// Select a policy by name: `count`, `position`, `regex:<pattern>`, `forbid:<a>,<b>,...` or
+/// `entropy:<bits>`.
+fn parse_policy(name: &str) -> crate::Result<Box<dyn PasswordPolicy>> {
+    let (kind, arg) = util::str_partition(name, ":");
+    match kind {
+        "count" => Ok(Box::new(CharCount)),
+        "position" => Ok(Box::new(CharPosition)),
+        "regex" => Regex::new(arg)
+            .map(|re| Box::new(MatchesRegex(re)) as Box<dyn PasswordPolicy>)
+            .map_err(|err| crate::Error::Parse(Box::new(err))),
+        "forbid" => Ok(Box::new(ForbiddenSubstrings(
+            arg.split(',').map(str::to_owned).collect(),
+        ))),
+        "entropy" => arg
+            .parse()
+            .map(|bits| Box::new(MinEntropy(bits)) as Box<dyn PasswordPolicy>)
+            .map_err(|err| crate::Error::Parse(Box::new(err))),
+        _ => Err(format!("unknown password policy: {}", name).into()),
+    }
+}
+
 fn read_input(input_path: PathBuf) -> crate::Result<Vec<Example>> {
     util::read_lines(&input_path)
         .map(|line| line.parse())
         .collect()
 }
 
-fn part1(input_path: PathBuf) -> crate::Result<String> {
+fn count_valid<P: PasswordPolicy>(input_path: PathBuf, policy: P) -> crate::Result<String> {
     let data = read_input(input_path)?;
     let valid_count = data
         .iter()
-        .filter(|example| example.policy.valid_by_char_count(&example.password))
+        .filter(|example| policy.is_valid(example))
         .count();
     Ok(valid_count.to_string())
 }
 
-fn part2(input_path: PathBuf) -> crate::Result<String> {
-    let data = read_input(input_path)?;
-    let valid_count = data
+/// List which passwords fail which of the policies selected by `policy_names`, one line per
+/// password that fails any of them.
+pub fn report<S: AsRef<str>>(input_path: PathBuf, policy_names: &[S]) -> crate::Result<String> {
+    let policies = policy_names
         .iter()
-        .filter(|example| example.policy.valid_by_char_position(&example.password))
-        .count();
-    Ok(valid_count.to_string())
+        .map(|name| parse_policy(name.as_ref()))
+        .collect::<crate::Result<Vec<_>>>()?;
+    let data = read_input(input_path)?;
+    let mut output = String::new();
+    for (i, example) in data.iter().enumerate() {
+        let failed: Vec<String> = policies
+            .iter()
+            .filter(|policy| !policy.is_valid(example))
+            .map(|policy| policy.name())
+            .collect();
+        if !failed.is_empty() {
+            output.push_str(&format!(
+                "line {}: {}: fails {}\n",
+                i + 1,
+                example.password,
+                failed.join(", ")
+            ));
+        }
+    }
+    Ok(output)
+}
+
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    count_valid(input_path, CharCount)
+}
+
+fn part2(input_path: PathBuf) -> crate::Result<String> {
+    count_valid(input_path, CharPosition)
 }
 
 pub fn register(runner: &mut crate::Runner) {
@@ -92,27 +232,64 @@ pub fn register(runner: &mut crate::Runner) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_part1_example() {
+        assert_eq!(part1(data_path!("day02_example.txt")).unwrap(), "2");
+    }
+
     #[test]
     fn test_part1_solution() {
         assert_eq!(part1(data_path!("day02_input.txt")).unwrap(), "454");
     }
 
+    #[test]
+    fn test_part2_example() {
+        assert_eq!(part2(data_path!("day02_example.txt")).unwrap(), "1");
+    }
+
     #[test]
     fn test_part2_validation_a() {
         let example: Example = "1-3 a: abcdef".parse().unwrap();
-        assert!(example.policy.valid_by_char_position(&example.password));
+        assert!(CharPosition.is_valid(&example));
     }
 
     #[test]
     fn test_part2_validation_b() {
         let example: Example = "1-3 b: cdefg".parse().unwrap();
-        assert!(!example.policy.valid_by_char_position(&example.password));
+        assert!(!CharPosition.is_valid(&example));
     }
 
     #[test]
     fn test_part2_validation_c() {
         let example: Example = "2-9 c: ccccccccc".parse().unwrap();
-        assert!(!example.policy.valid_by_char_position(&example.password));
+        assert!(!CharPosition.is_valid(&example));
+    }
+
+    #[test]
+    fn test_other_policies() {
+        let example: Example = "1-3 a: abcabc".parse().unwrap();
+        assert!(parse_policy("regex:^[abc]+$").unwrap().is_valid(&example));
+        assert!(!parse_policy("regex:^a+$").unwrap().is_valid(&example));
+        assert!(parse_policy("forbid:cc,bb").unwrap().is_valid(&example));
+        assert!(!parse_policy("forbid:cc,ca").unwrap().is_valid(&example));
+        // Three equally frequent characters, each worth log2(3) bits, six times over
+        assert!((MinEntropy::entropy("abcabc") - 6.0 * 3_f64.log2()).abs() < 1e-9);
+        assert_eq!(MinEntropy::entropy("aaaa"), 0.0);
+        assert!(parse_policy("entropy:9.5").unwrap().is_valid(&example));
+        assert!(!parse_policy("entropy:9.6").unwrap().is_valid(&example));
+        assert!(parse_policy("entropy:lots").is_err());
+        assert!(parse_policy("regex:(").is_err());
+        assert!(parse_policy("length").is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        let report = report(data_path!("day02_example.txt"), &["count", "position"]).unwrap();
+        assert_eq!(
+            report,
+            "line 2: cdefg: fails count, position\n\
+             line 3: ccccccccc: fails position\n"
+        );
     }
 
     #[test]
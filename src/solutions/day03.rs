@@ -1,112 +1,227 @@
+use std::ops::RangeInclusive;
+
+use num::Integer;
+
 use super::prelude::*;
-use crate::util::Rect;
+use crate::util::{self, Grid2D};
 use crate::vector::{Vector, Vector2D};
 
-const OPEN: u8 = '.' as u8;
-const TREE: u8 = '#' as u8;
-const NEWLINE: u8 = '\n' as u8;
-
 #[derive(Clone, Copy, Debug)]
 enum Tile {
     Open,
     Tree,
 }
 
-struct Map {
-    tiles: Vec<Tile>,
-    bounds: Rect<usize>,
-}
+/// A slope as `(right, down)`, where `down` must be positive and `right` may be negative.
+type Slope = Vector2D<i64>;
+
+/// A map that repeats infinitely to the left and right.
+struct Map(Grid2D<Tile>);
 
 impl Map {
-    fn at(&self, point: Vector2D<usize>) -> Option<Tile> {
-        let index = self.bounds.row_major_index(point)?;
-        Some(self.tiles[index])
+    fn check_slope(slope: Slope) -> crate::Result<()> {
+        if slope[1] > 0 {
+            Ok(())
+        } else {
+            Err(format!("slope must move down the map: {:?}", slope).into())
+        }
     }
 
-    fn traverse(&self, slope: Vector2D<usize>) -> impl Iterator<Item = Tile> + '_ {
-        let start: Vector2D<usize> = Vector([0, 0]);
-        std::iter::successors(Some(start), move |&p| {
-            let new = self.bounds.wrap_x(p + slope);
-            if self.bounds.contains(new) {
-                Some(new)
-            } else {
-                None
-            }
+    /// Tiles passed through on the way down the map from the top left, including the start.
+    fn traverse(&self, slope: Slope) -> crate::Result<impl Iterator<Item = Tile> + '_> {
+        Self::check_slope(slope)?;
+        let extent = self.0.extent();
+        Ok(std::iter::successors(Some(Vector([0, 0])), move |&p| {
+            Some(extent.wrap_x(p + slope))
         })
-        .map(move |p| self.at(p).unwrap())
+        .map_while(move |p| self.0.get(p).cloned()))
     }
-}
 
-fn read_input(input_path: PathBuf) -> crate::Result<Map> {
-    let file = File::open(input_path)?;
-    let mut width: Option<usize> = None;
-    let data: Vec<Tile> = file
-        .bytes()
-        .enumerate()
-        .filter_map(|(i, b)| match b.expect("read error") {
-            OPEN => Some(Tile::Open),
-            TREE => Some(Tile::Tree),
-            NEWLINE => {
-                if width.is_none() {
-                    width = Some(i);
+    fn count_trees(&self, slope: Slope) -> crate::Result<usize> {
+        Ok(self
+            .traverse(slope)?
+            .filter(|t| matches!(t, Tile::Tree))
+            .count())
+    }
+
+    /// Count trees for every slope in a single pass over the rows of the map.
+    ///
+    /// On row `y`, a slope `(dx, dy)` has taken `y / dy` steps if `y` is a multiple of `dy`, which
+    /// puts it at column `y / dy * dx`, wrapped to the width of the map.
+    fn count_trees_many(&self, slopes: &[Slope]) -> crate::Result<Vec<usize>> {
+        for &slope in slopes.iter() {
+            Self::check_slope(slope)?;
+        }
+        let Vector([width, height]) = self.0.size();
+        let mut counts = vec![0; slopes.len()];
+        for y in 0..height {
+            for (count, slope) in counts.iter_mut().zip(slopes.iter()) {
+                let (steps, rem) = y.div_rem(&slope[1]);
+                if rem == 0 {
+                    let x = (steps * slope[0]).mod_floor(&width);
+                    if let Some(Tile::Tree) = self.0.get(Vector([x, y])) {
+                        *count += 1;
+                    }
                 }
-                None
             }
-            b => panic!("unexpected byte {:?}", b),
+        }
+        Ok(counts)
+    }
+
+    /// Search every slope with `right` and `down` in the given ranges for the one that passes the
+    /// fewest trees, preferring the first found in order of `down` then `right`.
+    fn fewest_trees(
+        &self,
+        right: RangeInclusive<i64>,
+        down: RangeInclusive<i64>,
+    ) -> crate::Result<(Slope, usize)> {
+        let slopes: Vec<Slope> = down
+            .flat_map(|dy| right.clone().map(move |dx| Vector([dx, dy])))
+            .collect();
+        let counts = self.count_trees_many(&slopes)?;
+        slopes
+            .into_iter()
+            .zip(counts)
+            .min_by_key(|&(_, count)| count)
+            .ok_or_else(|| "no slopes to search".into())
+    }
+}
+
+fn read_input(input_path: PathBuf) -> crate::Result<Map> {
+    let rows = util::read_lines(&input_path)
+        .map(|line| {
+            line.bytes()
+                .map(|b| match b {
+                    b'.' => Ok(Tile::Open),
+                    b'#' => Ok(Tile::Tree),
+                    b => Err(format!("unexpected byte {:?}", b).into()),
+                })
+                .collect::<crate::Result<Vec<_>>>()
         })
-        .collect();
-    let width = width.expect("didn't find a newline");
-    let height = data.len() / width;
-    let rect = Rect(Vector([width, height]));
-    Ok(Map {
-        tiles: data,
-        bounds: rect,
-    })
+        .collect::<crate::Result<Vec<_>>>()?;
+    // Wrapping horizontally needs at least one column
+    if rows.first().is_some_and(|row| row.is_empty()) {
+        return Err(crate::Error::Parse("map must be at least 1 wide".into()));
+    }
+    Ok(Map(Grid2D::from_rows(rows)?))
 }
 
+/// Part 1: "Starting at the top-left corner of your map and following a slope of right 3 and down
+/// 1, how many trees would you encounter?"
 fn part1(input_path: PathBuf) -> crate::Result<String> {
     let map = read_input(input_path)?;
-    let tree_count = map
-        .traverse(Vector([3, 1]))
-        .filter(|t| matches!(t, Tile::Tree))
-        .count();
-    Ok(tree_count.to_string())
+    map.count_trees(Vector([3, 1]))
+        .map(|count| count.to_string())
 }
 
+/// Part 2: "What do you get if you multiply together the number of trees encountered on each of the
+/// listed slopes?"
 fn part2(input_path: PathBuf) -> crate::Result<String> {
     let map = read_input(input_path)?;
-    let slopes: Vec<Vector2D<usize>> = vec![
+    let slopes: Vec<Slope> = vec![
         Vector([1, 1]),
         Vector([3, 1]),
         Vector([5, 1]),
         Vector([7, 1]),
         Vector([1, 2]),
     ];
-    let product: usize = slopes
-        .into_iter()
-        .map(|slope| {
-            map.traverse(slope)
-                .filter(|t| matches!(t, Tile::Tree))
-                .count()
-        })
-        .product();
+    let product: usize = map.count_trees_many(&slopes)?.into_iter().product();
     Ok(product.to_string())
 }
 
+/// Find the slope that passes the fewest trees, going at most 10 right or left for each of up to 5
+/// down.
+fn fewest_trees(input_path: PathBuf) -> crate::Result<String> {
+    let map = read_input(input_path)?;
+    let (slope, count) = map.fewest_trees(-10..=10, 1..=5)?;
+    Ok(format!(
+        "right {}, down {}: {} trees",
+        slope[0], slope[1], count
+    ))
+}
+
 pub fn register(runner: &mut crate::Runner) {
     runner.add("day03part1", || part1(data_path!("day03_input.txt")));
     runner.add("day03part2", || part2(data_path!("day03_input.txt")));
+    runner.add("day03fewest_trees", || {
+        fewest_trees(data_path!("day03_input.txt"))
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_part1_example() {
+        assert_eq!(part1(data_path!("day03_example.txt")).unwrap(), "7");
+    }
+
     #[test]
     fn test_part1_solution() {
         assert_eq!(part1(data_path!("day03_input.txt")).unwrap(), "193");
     }
 
+    #[test]
+    fn test_count_trees_many() {
+        let map = read_input(data_path!("day03_example.txt")).unwrap();
+        let slopes: Vec<Slope> = vec![
+            Vector([1, 1]),
+            Vector([3, 1]),
+            Vector([5, 1]),
+            Vector([7, 1]),
+            Vector([1, 2]),
+            Vector([-3, 1]),
+            Vector([-1, 2]),
+            Vector([0, 3]),
+            Vector([25, 1]),
+        ];
+        let counts = map.count_trees_many(&slopes).unwrap();
+        assert_eq!(&counts[..5], &[2, 7, 3, 4, 2]);
+        for (slope, count) in slopes.iter().zip(counts) {
+            assert_eq!(map.count_trees(*slope).unwrap(), count, "{:?}", slope);
+        }
+        assert!(map.count_trees(Vector([1, 0])).is_err());
+        assert!(map.count_trees_many(&[Vector([1, -1])]).is_err());
+    }
+
+    #[test]
+    fn test_negative_slope_mirrors() {
+        // Going left on a mirrored map passes the same trees as going right on the original
+        let map = read_input(data_path!("day03_example.txt")).unwrap();
+        let size = map.0.size();
+        let mirrored = Map(Grid2D::from_fn(size, |p| {
+            let x = (size[0] - p[0]) % size[0];
+            *map.0.get(Vector([x, p[1]])).unwrap()
+        }));
+        for dx in 1..=7 {
+            assert_eq!(
+                mirrored.count_trees(Vector([-dx, 1])).unwrap(),
+                map.count_trees(Vector([dx, 1])).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_zero_width_map() {
+        assert!(matches!(
+            read_input(data_path!("day03_empty_rows.txt")),
+            Err(crate::Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_fewest_trees_example() {
+        let map = read_input(data_path!("day03_example.txt")).unwrap();
+        let (slope, count) = map.fewest_trees(1..=7, 1..=1).unwrap();
+        assert_eq!((slope, count), (Vector([2, 1]), 1));
+    }
+
+    #[test]
+    fn test_part2_example() {
+        assert_eq!(part2(data_path!("day03_example.txt")).unwrap(), "336");
+    }
+
     #[test]
     fn test_part2_solution() {
         assert_eq!(part2(data_path!("day03_input.txt")).unwrap(), "1355323200");
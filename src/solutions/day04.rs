@@ -1,81 +1,184 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::RangeInclusive;
 use std::str::FromStr;
 
+use regex::Regex;
+
 use super::prelude::*;
 use crate::util;
 
-#[allow(dead_code)]
-const EXPECTED_FIELDS: &'static [&'static str] =
-    &["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+/// The passport fields from the puzzle, where `cid` is optional "because it's a North Pole
+/// Credential".
+const PUZZLE_SCHEMA: &str = "\
+byr required int 1920..=2002
+iyr required int 2010..=2020
+eyr required int 2020..=2030
+hgt required units cm:150..=193 in:59..=76
+hcl required regex ^#[0-9a-f]{6}$
+ecl required enum amb blu brn gry grn hzl oth
+pid required regex ^[0-9]{9}$
+cid optional";
 
-const REQUIRED_FIELDS: &'static [&'static str] = &[
-    "byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid",
-    // "cid",
-];
+fn parse_range(s: &str) -> crate::Result<RangeInclusive<i64>> {
+    let (start, end) = util::str_partition(s, "..=");
+    Ok(start.parse()?..=end.parse()?)
+}
 
-type Validator = fn(&String) -> bool;
+/// A rule that a field's value must satisfy.
+#[derive(Debug)]
+enum Rule {
+    Any,
+    /// An integer in a range, e.g. `int 1920..=2002`.
+    Int(RangeInclusive<i64>),
+    /// An integer followed by a unit, where each unit has its own range, e.g.
+    /// `units cm:150..=193 in:59..=76`.
+    Units(Vec<(String, RangeInclusive<i64>)>),
+    /// Matches a regular expression, e.g. `regex ^[0-9]{9}$`.
+    Regex(Regex),
+    /// One of a fixed set of values, e.g. `enum amb blu`.
+    Enum(Vec<String>),
+}
 
-fn validate_range<I: FromStr + PartialOrd>(v: &str, range: RangeInclusive<I>) -> bool {
-    match v.parse::<I>() {
-        Ok(year) => range.contains(&year),
-        Err(_) => false,
+impl Rule {
+    fn check(&self, value: &str) -> bool {
+        match self {
+            Rule::Any => true,
+            Rule::Int(range) => value.parse().is_ok_and(|x| range.contains(&x)),
+            Rule::Units(units) => units.iter().any(|(unit, range)| {
+                value
+                    .strip_suffix(unit.as_str())
+                    .is_some_and(|amount| amount.parse().is_ok_and(|x| range.contains(&x)))
+            }),
+            Rule::Regex(re) => re.is_match(value),
+            Rule::Enum(options) => options.iter().any(|option| option == value),
+        }
     }
 }
 
-fn validate_byr(v: &String) -> bool {
-    validate_range::<u16>(v, 1920..=2002)
-}
+impl FromStr for Rule {
+    type Err = crate::Error;
 
-fn validate_iyr(v: &String) -> bool {
-    validate_range::<u16>(v, 2010..=2020)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, args) = util::str_partition(s.trim(), " ");
+        match kind {
+            "" => Ok(Rule::Any),
+            "int" => Ok(Rule::Int(parse_range(args)?)),
+            "units" => args
+                .split_whitespace()
+                .map(|arg| {
+                    let (unit, range) = util::str_partition(arg, ":");
+                    Ok((unit.to_owned(), parse_range(range)?))
+                })
+                .collect::<crate::Result<Vec<_>>>()
+                .map(Rule::Units),
+            "regex" => Regex::new(args)
+                .map(Rule::Regex)
+                .map_err(|err| crate::Error::Parse(Box::new(err))),
+            "enum" => Ok(Rule::Enum(
+                args.split_whitespace().map(str::to_owned).collect(),
+            )),
+            _ => Err(format!("unknown rule: {}", s).into()),
+        }
+    }
 }
 
-fn validate_eyr(v: &String) -> bool {
-    validate_range::<u16>(v, 2020..=2030)
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Any => Ok(()),
+            Rule::Int(range) => write!(f, "int {}..={}", range.start(), range.end()),
+            Rule::Units(units) => {
+                write!(f, "units")?;
+                for (unit, range) in units.iter() {
+                    write!(f, " {}:{}..={}", unit, range.start(), range.end())?;
+                }
+                Ok(())
+            }
+            Rule::Regex(re) => write!(f, "regex {}", re),
+            Rule::Enum(options) => write!(f, "enum {}", options.join(" ")),
+        }
+    }
 }
 
-fn validate_hgt(v: &String) -> bool {
-    let (amount, unit) = v.split_at(v.len() - 2);
-    let range = match unit {
-        "cm" => 150..=193,
-        "in" => 59..=76,
-        _ => return false,
-    };
-    validate_range::<u8>(amount, range)
+#[derive(Debug)]
+struct FieldSpec {
+    name: String,
+    required: bool,
+    rule: Rule,
 }
 
-fn validate_hex_char(b: &u8) -> bool {
-    (b'0'..=b'9').contains(b) || (b'a'..=b'f').contains(b)
+impl FromStr for FieldSpec {
+    type Err = crate::Error;
+
+    /// Parse a `<name> required|optional [<rule>]` line.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ' ');
+        let name = parts.next().unwrap_or("").to_owned();
+        let required = match parts.next() {
+            Some("required") => true,
+            Some("optional") => false,
+            _ => return Err(format!("expected required or optional: {}", s).into()),
+        };
+        let rule = parts.next().unwrap_or("").parse()?;
+        Ok(FieldSpec {
+            name,
+            required,
+            rule,
+        })
+    }
 }
 
-fn validate_hcl(v: &String) -> bool {
-    let v = v.as_bytes();
-    v.len() == 7 && v[0] == b'#' && v[1..].iter().all(validate_hex_char)
+/// The fields a passport may have, one `FieldSpec` per line.
+#[derive(Debug)]
+struct Schema {
+    fields: Vec<FieldSpec>,
 }
 
-fn validate_ecl(v: &String) -> bool {
-    match v.as_str() {
-        "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth" => true,
-        _ => false,
+impl FromStr for Schema {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.parse())
+            .collect::<crate::Result<Vec<_>>>()
+            .map(|fields| Schema { fields })
     }
 }
 
-fn validate_pid(v: &String) -> bool {
-    let v = v.as_bytes();
-    let range = b'0'..=b'9';
-    v.len() == 9 && v.iter().all(|b| range.contains(b))
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Violation {
+    Missing {
+        field: String,
+    },
+    Invalid {
+        field: String,
+        value: String,
+    },
+    /// A field not in the schema, which is reported but doesn't make a passport invalid.
+    Unexpected {
+        field: String,
+    },
+}
+
+impl Violation {
+    fn is_failure(&self) -> bool {
+        !matches!(self, Violation::Unexpected { .. })
+    }
 }
 
-const VALIDATORS: &'static [(&'static str, Validator)] = &[
-    ("byr", validate_byr as Validator),
-    ("iyr", validate_iyr as Validator),
-    ("eyr", validate_eyr as Validator),
-    ("hgt", validate_hgt as Validator),
-    ("hcl", validate_hcl as Validator),
-    ("ecl", validate_ecl as Validator),
-    ("pid", validate_pid as Validator),
-];
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Missing { field } => write!(f, "{}: missing", field),
+            Violation::Invalid { field, value } => {
+                write!(f, "{}: invalid value {:?}", field, value)
+            }
+            Violation::Unexpected { field } => write!(f, "{}: unexpected field", field),
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Passport {
@@ -83,19 +186,35 @@ struct Passport {
 }
 
 impl Passport {
-    fn has_required_fields(&self) -> bool {
-        REQUIRED_FIELDS
-            .iter()
-            .all(|k| self.data.contains_key(k.to_owned()))
-    }
-
-    fn is_valid(&self) -> bool {
-        VALIDATORS
-            .iter()
-            .all(|(field, validator)| match self.data.get(field.to_owned()) {
-                Some(value) => validator(value),
-                None => false,
-            })
+    /// Check every field against `schema`, ignoring values if `check_values` is false.
+    ///
+    /// Violations are in schema order, followed by unexpected fields in name order.
+    fn validate(&self, schema: &Schema, check_values: bool) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for spec in schema.fields.iter() {
+            match self.data.get(&spec.name) {
+                None if spec.required => violations.push(Violation::Missing {
+                    field: spec.name.clone(),
+                }),
+                Some(value) if check_values && !spec.rule.check(value) => {
+                    violations.push(Violation::Invalid {
+                        field: spec.name.clone(),
+                        value: value.clone(),
+                    })
+                }
+                _ => {}
+            }
+        }
+        let mut unexpected: Vec<&String> = self
+            .data
+            .keys()
+            .filter(|k| !schema.fields.iter().any(|spec| &spec.name == *k))
+            .collect();
+        unexpected.sort();
+        violations.extend(unexpected.into_iter().map(|field| Violation::Unexpected {
+            field: field.clone(),
+        }));
+        violations
     }
 }
 
@@ -127,19 +246,49 @@ fn read_input(input_path: PathBuf) -> crate::Result<Vec<Passport>> {
     Ok(output)
 }
 
-fn part1(input_path: PathBuf) -> crate::Result<String> {
+fn count_valid(input_path: PathBuf, check_values: bool) -> crate::Result<usize> {
+    let schema: Schema = PUZZLE_SCHEMA.parse()?;
     let passports = read_input(input_path)?;
-    let valid_count = passports
+    Ok(passports
         .iter()
-        .filter(|&p| p.has_required_fields())
-        .count();
-    Ok(valid_count.to_string())
+        .filter(|&p| {
+            p.validate(&schema, check_values)
+                .iter()
+                .all(|v| !v.is_failure())
+        })
+        .count())
 }
 
-fn part2(input_path: PathBuf) -> crate::Result<String> {
+/// Describe why each passport fails validation against `schema`, one line per passport with any
+/// violations. Unexpected fields are listed too, even for otherwise valid passports.
+fn describe_violations(passports: &[Passport], schema: &Schema) -> String {
+    let mut output = String::new();
+    for (i, passport) in passports.iter().enumerate() {
+        let violations = passport.validate(schema, true);
+        if !violations.is_empty() {
+            let reasons: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+            output.push_str(&format!("passport {}: {}\n", i, reasons.join(", ")));
+        }
+    }
+    output
+}
+
+/// Describe why each passport in the input fails the puzzle's validation rules.
+pub fn report(input_path: PathBuf) -> crate::Result<String> {
+    let schema: Schema = PUZZLE_SCHEMA.parse()?;
     let passports = read_input(input_path)?;
-    let valid_count = passports.iter().filter(|&p| p.is_valid()).count();
-    Ok(valid_count.to_string())
+    Ok(describe_violations(&passports, &schema))
+}
+
+/// Part 1: "Count the number of valid passports - those that have all required fields."
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    count_valid(input_path, false).map(|count| count.to_string())
+}
+
+/// Part 2: "Count the number of valid passports - those that have all required fields and valid
+/// values."
+fn part2(input_path: PathBuf) -> crate::Result<String> {
+    count_valid(input_path, true).map(|count| count.to_string())
 }
 
 pub fn register(runner: &mut crate::Runner) {
@@ -156,6 +305,78 @@ mod tests {
         assert_eq!(part1(data_path!("day04_example.txt")).unwrap(), "2");
     }
 
+    #[test]
+    fn test_rule() {
+        let hgt: Rule = "units cm:150..=193 in:59..=76".parse().unwrap();
+        assert!(hgt.check("60in"));
+        assert!(hgt.check("190cm"));
+        assert!(!hgt.check("190in"));
+        assert!(!hgt.check("190"));
+        assert_eq!(hgt.to_string(), "units cm:150..=193 in:59..=76");
+        let hcl: Rule = "regex ^#[0-9a-f]{6}$".parse().unwrap();
+        assert!(hcl.check("#123abc"));
+        assert!(!hcl.check("#123abz"));
+        assert!(!hcl.check("123abc"));
+        let byr: Rule = "int 1920..=2002".parse().unwrap();
+        assert!(byr.check("2002"));
+        assert!(!byr.check("2003"));
+        let ecl: Rule = "enum brn wat".parse().unwrap();
+        assert!(ecl.check("wat"));
+        assert!(!ecl.check("hzl"));
+        assert!("range 1..=2".parse::<Rule>().is_err());
+        assert!("int 1..2".parse::<Rule>().is_err());
+        assert!("regex (".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_violations() {
+        let schema: Schema = PUZZLE_SCHEMA.parse().unwrap();
+        let passports = read_input(data_path!("day04_example.txt")).unwrap();
+        assert!(passports[0].validate(&schema, true).is_empty());
+        assert_eq!(
+            passports[1].validate(&schema, true),
+            vec![Violation::Missing {
+                field: "hgt".into()
+            }]
+        );
+        assert_eq!(
+            report(data_path!("day04_example.txt")).unwrap(),
+            "passport 1: hgt: missing\npassport 3: byr: missing\n"
+        );
+
+        let mut passport = Passport {
+            data: passports[0].data.clone(),
+        };
+        passport.data.insert("ecl".into(), "red".into());
+        passport.data.insert("xyz".into(), "1".into());
+        assert_eq!(
+            passport
+                .validate(&schema, true)
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>(),
+            vec!["ecl: invalid value \"red\"", "xyz: unexpected field"]
+        );
+
+        // Extra fields are reported, but the passport is still valid
+        let mut passport = Passport {
+            data: passports[0].data.clone(),
+        };
+        passport.data.insert("xyz".into(), "1".into());
+        let violations = passport.validate(&schema, true);
+        assert_eq!(
+            violations,
+            vec![Violation::Unexpected {
+                field: "xyz".into()
+            }]
+        );
+        assert!(violations.iter().all(|v| !v.is_failure()));
+        assert_eq!(
+            describe_violations(&[passport], &schema),
+            "passport 0: xyz: unexpected field\n"
+        );
+    }
+
     #[test]
     fn test_part1_solution() {
         assert_eq!(part1(data_path!("day04_input.txt")).unwrap(), "182");
@@ -1,45 +1,177 @@
+use std::fmt;
+
 use super::prelude::*;
 
 use crate::util;
 
-fn decode_binary(v: &[u8], one: u8) -> u16 {
-    v.iter()
-        .fold(0, |acc, &c| (acc << 1) | if c == one { 1 } else { 0 })
+/// How many bits of a boarding pass encode the row and the column of a seat.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Layout {
+    row_bits: u32,
+    col_bits: u32,
+}
+
+impl Layout {
+    /// A layout for seat IDs of `row_bits + col_bits` bits, which must fit in a `u32`.
+    fn new(row_bits: u32, col_bits: u32) -> crate::Result<Self> {
+        if row_bits
+            .checked_add(col_bits)
+            .is_none_or(|bits| bits > u32::BITS)
+        {
+            return Err(parse_error(format!(
+                "{} row bits and {} column bits don't fit in a {}-bit seat ID",
+                row_bits,
+                col_bits,
+                u32::BITS
+            )));
+        }
+        Ok(Layout { row_bits, col_bits })
+    }
 }
 
-fn decode_seat(id: &[u8]) -> (u16, u16) {
-    (decode_binary(&id[..7], b'B'), decode_binary(&id[7..], b'R'))
+/// "The first 7 characters will either be F or B ... The last three characters will be either L or
+/// R".
+fn puzzle_layout() -> crate::Result<Layout> {
+    Layout::new(7, 3)
 }
 
-fn decode_seat_id(id: &[u8]) -> u16 {
-    let (row, col) = decode_seat(id);
-    (row << 3) | col
+fn parse_error(message: String) -> crate::Error {
+    crate::Error::Parse(message.into())
+}
+
+/// Check `value` only uses its lowest `bits` bits, where `bits` can be as many as are in a `u32`.
+fn fits_in(value: u32, bits: u32) -> bool {
+    value.checked_shr(bits).unwrap_or(0) == 0
+}
+
+/// Decode `v` as a binary number where `zero` and `one` are the digits, or return the position of
+/// the first invalid digit.
+fn decode_binary(v: &[u8], zero: u8, one: u8) -> Result<u32, usize> {
+    v.iter().enumerate().try_fold(0, |acc, (i, &c)| {
+        let bit = match c {
+            c if c == zero => 0,
+            c if c == one => 1,
+            _ => return Err(i),
+        };
+        Ok((acc << 1) | bit)
+    })
+}
+
+/// Encode the lowest `bits` bits of `value` as binary where `zero` and `one` are the digits.
+fn encode_binary(value: u32, bits: u32, zero: char, one: char) -> String {
+    (0..bits)
+        .rev()
+        .map(|i| if value & (1 << i) != 0 { one } else { zero })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct BoardingPass {
+    layout: Layout,
+    row: u32,
+    col: u32,
+}
+
+impl BoardingPass {
+    fn new(layout: Layout, row: u32, col: u32) -> crate::Result<Self> {
+        if !fits_in(row, layout.row_bits) || !fits_in(col, layout.col_bits) {
+            return Err(parse_error(format!(
+                "seat ({}, {}) is outside {:?}",
+                row, col, layout
+            )));
+        }
+        Ok(BoardingPass { layout, row, col })
+    }
+
+    /// The boarding pass for a seat ID, which is just the row and column bits concatenated.
+    fn from_seat_id(layout: Layout, id: u32) -> crate::Result<Self> {
+        let col_mask = u32::MAX
+            .checked_shr(u32::BITS - layout.col_bits)
+            .unwrap_or(0);
+        let row = id.checked_shr(layout.col_bits).unwrap_or(0);
+        BoardingPass::new(layout, row, id & col_mask)
+    }
+
+    fn decode(layout: Layout, s: &str) -> crate::Result<Self> {
+        let bytes = s.as_bytes();
+        let row_len = layout.row_bits as usize;
+        if bytes.len() != row_len + layout.col_bits as usize {
+            return Err(parse_error(format!(
+                "expected {} characters in boarding pass {:?}",
+                row_len + layout.col_bits as usize,
+                s
+            )));
+        }
+        let invalid = |pos: usize, expected: &str| {
+            parse_error(format!(
+                "expected {} at position {} in boarding pass {:?}",
+                expected, pos, s
+            ))
+        };
+        let row = decode_binary(&bytes[..row_len], b'F', b'B').map_err(|i| invalid(i, "F or B"))?;
+        let col = decode_binary(&bytes[row_len..], b'L', b'R')
+            .map_err(|i| invalid(row_len + i, "L or R"))?;
+        BoardingPass::new(layout, row, col)
+    }
+
+    fn seat_id(&self) -> u32 {
+        self.row.checked_shl(self.layout.col_bits).unwrap_or(0) | self.col
+    }
+}
+
+impl fmt::Display for BoardingPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            encode_binary(self.row, self.layout.row_bits, 'F', 'B'),
+            encode_binary(self.col, self.layout.col_bits, 'L', 'R')
+        )
+    }
+}
+
+fn read_seat_ids(input_path: &PathBuf, layout: Layout) -> crate::Result<Vec<u32>> {
+    util::read_lines(input_path)
+        .map(|line| BoardingPass::decode(layout, &line).map(|pass| pass.seat_id()))
+        .collect()
+}
+
+/// Find every seat ID missing from between the lowest and highest of `seats`, in order.
+fn find_gaps(seats: &[u32]) -> Vec<u32> {
+    let mut seats = seats.to_vec();
+    seats.sort_unstable();
+    seats.dedup();
+    seats
+        .windows(2)
+        .flat_map(|pair| (pair[0] + 1)..pair[1])
+        .collect()
 }
 
 fn part1(input_path: PathBuf) -> crate::Result<String> {
-    util::read_lines(&input_path)
-        .map(|id| decode_seat_id(id.as_bytes()))
+    read_seat_ids(&input_path, puzzle_layout()?)?
+        .into_iter()
         .max()
         .ok_or("no result found".into())
         .map(|id| id.to_string())
 }
 
+/// Part 2: "What is the ID of your seat?"
+///
+/// Seats at the very front and back don't exist, but they're outside the range of the seat IDs in
+/// the list, so the only gap inside it should be our own seat.
 fn part2(input_path: PathBuf) -> crate::Result<String> {
-    let mut seats: Vec<_> = util::read_lines(&input_path)
-        .map(|id| decode_seat_id(id.as_bytes()))
-        .collect();
-    seats.sort();
-    seats
-        .windows(2)
-        .find_map(|pair| {
-            if pair[1] == pair[0] + 2 {
-                Some(pair[0] + 1)
-            } else {
-                None
-            }
-        })
-        .ok_or("no result found".into())
-        .map(|id| id.to_string())
+    let layout = puzzle_layout()?;
+    let seats = read_seat_ids(&input_path, layout)?;
+    match find_gaps(&seats)[..] {
+        [id] => Ok(id.to_string()),
+        ref gaps => {
+            let passes = gaps
+                .iter()
+                .map(|&id| Ok(BoardingPass::from_seat_id(layout, id)?.to_string()))
+                .collect::<crate::Result<Vec<_>>>()?;
+            Err(format!("expected exactly one gap, found {}", passes.join(", ")).into())
+        }
+    }
 }
 
 pub fn register(runner: &mut crate::Runner) {
@@ -53,24 +185,98 @@ mod tests {
 
     #[test]
     fn test_decode_binary() {
-        assert_eq!(decode_binary(b"FBFBBFF", b'B'), 44);
-        assert_eq!(decode_binary(b"RLR", b'R'), 5);
+        assert_eq!(decode_binary(b"FBFBBFF", b'F', b'B'), Ok(44));
+        assert_eq!(decode_binary(b"RLR", b'L', b'R'), Ok(5));
+        assert_eq!(decode_binary(b"FBXB", b'F', b'B'), Err(2));
     }
 
     #[test]
     fn test_decode_seat() {
-        assert_eq!(decode_seat(b"FBFBBFFRLR"), (44, 5));
-        assert_eq!(decode_seat(b"BFFFBBFRRR"), (70, 7));
-        assert_eq!(decode_seat(b"FFFBBBFRRR"), (14, 7));
-        assert_eq!(decode_seat(b"BBFFBBFRLL"), (102, 4));
+        let decode = |s| {
+            let pass = BoardingPass::decode(puzzle_layout().unwrap(), s).unwrap();
+            (pass.row, pass.col)
+        };
+        assert_eq!(decode("FBFBBFFRLR"), (44, 5));
+        assert_eq!(decode("BFFFBBFRRR"), (70, 7));
+        assert_eq!(decode("FFFBBBFRRR"), (14, 7));
+        assert_eq!(decode("BBFFBBFRLL"), (102, 4));
+        // Row and column letters can't be swapped
+        let err = BoardingPass::decode(puzzle_layout().unwrap(), "FBFBBFFRLB")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "parse error: expected L or R at position 9 in boarding pass \"FBFBBFFRLB\""
+        );
+        assert!(BoardingPass::decode(puzzle_layout().unwrap(), "RBFBBFFRLR").is_err());
+        assert!(BoardingPass::decode(puzzle_layout().unwrap(), "FBFBBFFRL").is_err());
     }
 
     #[test]
     fn test_decode_seat_id() {
-        assert_eq!(decode_seat_id(b"FBFBBFFRLR"), 357);
-        assert_eq!(decode_seat_id(b"BFFFBBFRRR"), 567);
-        assert_eq!(decode_seat_id(b"FFFBBBFRRR"), 119);
-        assert_eq!(decode_seat_id(b"BBFFBBFRLL"), 820);
+        let decode = |s| {
+            BoardingPass::decode(puzzle_layout().unwrap(), s)
+                .unwrap()
+                .seat_id()
+        };
+        assert_eq!(decode("FBFBBFFRLR"), 357);
+        assert_eq!(decode("BFFFBBFRRR"), 567);
+        assert_eq!(decode("FFFBBBFRRR"), 119);
+        assert_eq!(decode("BBFFBBFRLL"), 820);
+    }
+
+    #[test]
+    fn test_encode() {
+        for s in ["FBFBBFFRLR", "BFFFBBFRRR", "FFFBBBFRRR", "BBFFBBFRLL"].iter() {
+            let pass = BoardingPass::decode(puzzle_layout().unwrap(), s).unwrap();
+            assert_eq!(pass.to_string(), *s);
+            let from_id =
+                BoardingPass::from_seat_id(puzzle_layout().unwrap(), pass.seat_id()).unwrap();
+            assert_eq!(from_id, pass);
+        }
+        assert!(BoardingPass::from_seat_id(puzzle_layout().unwrap(), 1024).is_err());
+    }
+
+    #[test]
+    fn test_other_layout() {
+        let layout = Layout::new(3, 5).unwrap();
+        let pass = BoardingPass::from_seat_id(layout, 0b101_10011).unwrap();
+        assert_eq!((pass.row, pass.col), (5, 19));
+        assert_eq!(pass.to_string(), "BFBRLLRR");
+        assert_eq!(BoardingPass::decode(layout, "BFBRLLRR").unwrap(), pass);
+        assert!(BoardingPass::new(layout, 8, 0).is_err());
+    }
+
+    #[test]
+    fn test_layout_limits() {
+        assert!(matches!(Layout::new(30, 3), Err(crate::Error::Parse(_))));
+        assert!(matches!(Layout::new(32, 1), Err(crate::Error::Parse(_))));
+        assert!(matches!(
+            Layout::new(u32::MAX, 1),
+            Err(crate::Error::Parse(_))
+        ));
+
+        let rows_only = Layout::new(32, 0).unwrap();
+        let pass = BoardingPass::from_seat_id(rows_only, u32::MAX).unwrap();
+        assert_eq!((pass.row, pass.col), (u32::MAX, 0));
+        assert_eq!(pass.seat_id(), u32::MAX);
+        assert_eq!(pass.to_string(), "B".repeat(32));
+
+        let cols_only = Layout::new(0, 32).unwrap();
+        let pass = BoardingPass::from_seat_id(cols_only, 5).unwrap();
+        assert_eq!((pass.row, pass.col), (0, 5));
+        assert_eq!(pass.seat_id(), 5);
+        assert!(matches!(
+            BoardingPass::new(cols_only, 1, 0),
+            Err(crate::Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        assert_eq!(find_gaps(&[10, 4, 5, 8, 7, 12]), vec![6, 9, 11]);
+        assert_eq!(find_gaps(&[1, 2, 3]), vec![]);
+        assert_eq!(find_gaps(&[]), vec![]);
     }
 
     #[test]
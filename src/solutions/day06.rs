@@ -5,47 +5,142 @@ use crate::util;
 
 type Question = u8;
 
-struct Person(HashSet<Question>);
-
-struct Group(Vec<Person>);
-
-impl Group {
-    fn question_union(&self) -> HashSet<Question> {
-        let mut iter = self.0.iter().map(|p| &p.0);
-        let mut acc = iter.next().cloned().unwrap_or(HashSet::new());
-        for other in iter {
-            for &q in other {
-                acc.insert(q);
-            }
-        }
-        acc
+/// Check that `question` is one of `a` to `z`, so every `AnswerSet` accepts the same input.
+fn check_question(question: Question) -> crate::Result<Question> {
+    if question.is_ascii_lowercase() {
+        Ok(question)
+    } else {
+        Err(format!("unexpected question: {:?}", question as char).into())
+    }
+}
+
+/// The questions answered "yes" by a person, or by a group in some combination.
+trait AnswerSet: Clone + Sized {
+    fn from_answers(answers: &[Question]) -> crate::Result<Self>;
+    fn contains(&self, question: Question) -> bool;
+    fn union(&self, other: &Self) -> Self;
+    fn intersection(&self, other: &Self) -> Self;
+    fn difference(&self, other: &Self) -> Self;
+    fn len(&self) -> usize;
+}
+
+impl AnswerSet for HashSet<Question> {
+    fn from_answers(answers: &[Question]) -> crate::Result<Self> {
+        answers.iter().map(|&q| check_question(q)).collect()
+    }
+
+    fn contains(&self, question: Question) -> bool {
+        HashSet::contains(self, &question)
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        HashSet::union(self, other).cloned().collect()
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        HashSet::intersection(self, other).cloned().collect()
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        HashSet::difference(self, other).cloned().collect()
+    }
+
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+}
+
+/// Set of questions `a` to `z` as a bitset, where bit 0 is question `a`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct QuestionSet(u32);
+
+impl QuestionSet {
+    fn bit(question: Question) -> u32 {
+        1 << (question - b'a')
+    }
+}
+
+impl AnswerSet for QuestionSet {
+    fn from_answers(answers: &[Question]) -> crate::Result<Self> {
+        answers.iter().try_fold(QuestionSet(0), |set, &q| {
+            Ok(QuestionSet(set.0 | Self::bit(check_question(q)?)))
+        })
+    }
+
+    fn contains(&self, question: Question) -> bool {
+        question.is_ascii_lowercase() && self.0 & Self::bit(question) != 0
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        QuestionSet(self.0 | other.0)
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        QuestionSet(self.0 & other.0)
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        QuestionSet(self.0 & !other.0)
+    }
+
+    fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+}
+
+/// The answers of each person in a group.
+struct Group<S: AnswerSet>(Vec<S>);
+
+impl<S: AnswerSet> Group<S> {
+    fn combine<F: Fn(&S, &S) -> S>(&self, f: F) -> Option<S> {
+        let mut iter = self.0.iter();
+        let first = iter.next()?.clone();
+        Some(iter.fold(first, |acc, other| f(&acc, other)))
+    }
+
+    /// Questions anyone in the group answered.
+    fn question_union(&self) -> Option<S> {
+        self.combine(S::union)
+    }
+
+    /// Questions everyone in the group answered.
+    fn question_intersection(&self) -> Option<S> {
+        self.combine(S::intersection)
+    }
+
+    /// Questions someone in the group answered but someone else didn't.
+    fn question_disagreement(&self) -> Option<S> {
+        Some(
+            self.question_union()?
+                .difference(&self.question_intersection()?),
+        )
     }
 
-    fn question_intersection(&self) -> HashSet<Question> {
-        let mut iter = self.0.iter().map(|p| &p.0);
-        let mut acc = iter.next().cloned().unwrap_or(HashSet::new());
-        for other in iter {
-            acc.retain(|q| other.contains(q));
+    /// How many people in the group answered each question from `a` to `z`.
+    fn question_frequencies(&self) -> [usize; 26] {
+        let mut frequencies = [0; 26];
+        for (question, frequency) in (b'a'..=b'z').zip(frequencies.iter_mut()) {
+            *frequency = self.0.iter().filter(|s| s.contains(question)).count();
         }
-        acc
+        frequencies
     }
 }
 
-fn read_input(input_path: &PathBuf) -> crate::Result<Vec<Group>> {
-    let mut output: Vec<Group> = Vec::new();
-    let mut current: Vec<Person> = Vec::new();
+fn read_input<S: AnswerSet>(input_path: &PathBuf) -> crate::Result<Vec<Group<S>>> {
+    let mut output: Vec<Group<S>> = Vec::new();
+    let mut current: Vec<S> = Vec::new();
 
-    fn finalise(current: &mut Vec<Person>, output: &mut Vec<Group>) {
-        if current.len() > 0 {
-            output.push(Group(current.drain(..).collect()));
+    fn finalise<S: AnswerSet>(current: &mut Vec<S>, output: &mut Vec<Group<S>>) {
+        if !current.is_empty() {
+            output.push(Group(std::mem::take(current)));
         }
     }
 
     for line in util::read_lines(input_path) {
-        if line.len() == 0 {
+        if line.is_empty() {
             finalise(&mut current, &mut output);
         } else {
-            current.push(Person(line.bytes().collect()));
+            current.push(S::from_answers(line.as_bytes())?);
         }
     }
     finalise(&mut current, &mut output);
@@ -53,43 +148,164 @@ fn read_input(input_path: &PathBuf) -> crate::Result<Vec<Group>> {
     Ok(output)
 }
 
-fn part1(input_path: PathBuf) -> crate::Result<String> {
-    Ok(read_input(&input_path)?
+fn sum_counts<S, F>(input_path: PathBuf, combine: F) -> crate::Result<String>
+where
+    S: AnswerSet,
+    F: Fn(&Group<S>) -> Option<S>,
+{
+    Ok(read_input::<S>(&input_path)?
         .iter()
-        .map(|group| group.question_union().len())
+        .map(|group| combine(group).map_or(0, |s| s.len()))
         .sum::<usize>()
         .to_string())
 }
 
-fn part2(input_path: PathBuf) -> crate::Result<String> {
-    Ok(read_input(&input_path)?
-        .iter()
-        .map(|group| group.question_intersection().len())
-        .sum::<usize>()
-        .to_string())
+/// Part 1: "For each group, count the number of questions to which anyone answered "yes". What is
+/// the sum of those counts?"
+fn part1<S: AnswerSet>(input_path: PathBuf) -> crate::Result<String> {
+    sum_counts(input_path, Group::<S>::question_union)
+}
+
+/// Part 2: "For each group, count the number of questions to which everyone answered "yes". What is
+/// the sum of those counts?"
+fn part2<S: AnswerSet>(input_path: PathBuf) -> crate::Result<String> {
+    sum_counts(input_path, Group::<S>::question_intersection)
+}
+
+/// Frequency stats across all groups: the most commonly answered question and how many people
+/// answered it, and how many questions were answered by only part of their group.
+fn question_stats(input_path: PathBuf) -> crate::Result<String> {
+    let mut totals = [0; 26];
+    let mut disagreements = 0;
+    for group in read_input::<QuestionSet>(&input_path)? {
+        for (total, frequency) in totals.iter_mut().zip(group.question_frequencies().iter()) {
+            *total += frequency;
+        }
+        disagreements += group.question_disagreement().map_or(0, |s| s.len());
+    }
+    let (question, count) = (b'a'..=b'z')
+        .zip(totals.iter())
+        .max_by_key(|&(_, count)| count)
+        .unwrap();
+    Ok(format!(
+        "most common {} ({} people), {} disagreements",
+        question as char, count, disagreements
+    ))
 }
 
 pub fn register(runner: &mut crate::Runner) {
-    runner.add("day06part1", || part1(data_path!("day06_input.txt")));
-    runner.add("day06part2", || part2(data_path!("day06_input.txt")));
+    runner.add("day06part1_bitset", || {
+        part1::<QuestionSet>(data_path!("day06_input.txt"))
+    });
+    runner.add("day06part1_hashset", || {
+        part1::<HashSet<Question>>(data_path!("day06_input.txt"))
+    });
+    runner.add("day06part2_bitset", || {
+        part2::<QuestionSet>(data_path!("day06_input.txt"))
+    });
+    runner.add("day06part2_hashset", || {
+        part2::<HashSet<Question>>(data_path!("day06_input.txt"))
+    });
+    runner.add("day06stats", || {
+        question_stats(data_path!("day06_input.txt"))
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_question_set() {
+        let abc = QuestionSet::from_answers(b"abc").unwrap();
+        let bcz = QuestionSet::from_answers(b"bcz").unwrap();
+        assert_eq!(abc.union(&bcz), QuestionSet::from_answers(b"abcz").unwrap());
+        assert_eq!(
+            abc.intersection(&bcz),
+            QuestionSet::from_answers(b"cb").unwrap()
+        );
+        assert_eq!(
+            abc.difference(&bcz),
+            QuestionSet::from_answers(b"a").unwrap()
+        );
+        assert_eq!(abc.union(&bcz).len(), 4);
+        assert!(bcz.contains(b'z'));
+        assert!(!bcz.contains(b'a'));
+        assert!(QuestionSet::from_answers(b"aB").is_err());
+    }
+
+    #[test]
+    fn test_answer_sets_agree_on_invalid_questions() {
+        for answers in [&b"aB"[..], b"a1", b"a b", b"\xff"] {
+            let by_bits = QuestionSet::from_answers(answers).err().unwrap();
+            let by_hash = HashSet::<Question>::from_answers(answers).err().unwrap();
+            assert_eq!(by_bits.to_string(), by_hash.to_string());
+        }
+        assert!(HashSet::<Question>::from_answers(b"abz").is_ok());
+    }
+
+    #[test]
+    fn test_group_stats() {
+        let groups = read_input::<QuestionSet>(&data_path!("day06_example.txt")).unwrap();
+        // "ab", "ac"
+        let group = &groups[2];
+        assert_eq!(group.question_disagreement().unwrap().len(), 2);
+        assert_eq!(&group.question_frequencies()[..4], &[2, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_question_stats_example() {
+        assert_eq!(
+            question_stats(data_path!("day06_example.txt")).unwrap(),
+            "most common a (8 people), 5 disagreements"
+        );
+    }
+
     #[test]
     fn test_part1_example() {
-        assert_eq!(part1(data_path!("day06_example.txt")).unwrap(), "11");
+        assert_eq!(
+            part1::<QuestionSet>(data_path!("day06_example.txt")).unwrap(),
+            "11"
+        );
+        assert_eq!(
+            part1::<HashSet<Question>>(data_path!("day06_example.txt")).unwrap(),
+            "11"
+        );
     }
 
     #[test]
     fn test_part1_solution() {
-        assert_eq!(part1(data_path!("day06_input.txt")).unwrap(), "6551");
+        assert_eq!(
+            part1::<QuestionSet>(data_path!("day06_input.txt")).unwrap(),
+            "6551"
+        );
+        assert_eq!(
+            part1::<HashSet<Question>>(data_path!("day06_input.txt")).unwrap(),
+            "6551"
+        );
+    }
+
+    #[test]
+    fn test_part2_example() {
+        assert_eq!(
+            part2::<QuestionSet>(data_path!("day06_example.txt")).unwrap(),
+            "6"
+        );
+        assert_eq!(
+            part2::<HashSet<Question>>(data_path!("day06_example.txt")).unwrap(),
+            "6"
+        );
     }
 
     #[test]
     fn test_part2_solution() {
-        assert_eq!(part2(data_path!("day06_input.txt")).unwrap(), "3358");
+        assert_eq!(
+            part2::<QuestionSet>(data_path!("day06_input.txt")).unwrap(),
+            "3358"
+        );
+        assert_eq!(
+            part2::<HashSet<Question>>(data_path!("day06_input.txt")).unwrap(),
+            "3358"
+        );
     }
 }
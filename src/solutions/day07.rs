@@ -1,12 +1,16 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt::Write;
 use std::str::FromStr;
 
+use num::{BigUint, One, Zero};
+
 use super::prelude::*;
 use crate::util;
 
 struct Rule {
     outer: String,
-    inner: HashMap<String, u8>,
+    /// Bags directly inside `outer`, and how many of each, in name order.
+    inner: BTreeMap<String, u64>,
 }
 
 impl FromStr for Rule {
@@ -16,15 +20,15 @@ impl FromStr for Rule {
         let (outer_raw, inner_raw) = util::str_partition(s, " bags contain ");
         let outer: String = outer_raw.to_owned();
         let inner = if inner_raw == "no other bags." {
-            HashMap::new()
+            BTreeMap::new()
         } else {
             inner_raw
                 .split(", ")
-                .map(|def| -> Result<(String, u8), Self::Err> {
+                .map(|def| -> Result<(String, u64), Self::Err> {
                     // Strip trailing "bag(s?)"
                     let (def, _) = util::str_rpartition(def, " ");
                     let (count_raw, bag_def_raw) = util::str_partition(def, " ");
-                    let count: u8 = count_raw.parse()?;
+                    let count: u64 = count_raw.parse()?;
                     let bag_def: String = bag_def_raw.to_owned();
                     Ok((bag_def, count))
                 })
@@ -39,13 +43,6 @@ struct Ruleset {
 }
 
 impl Ruleset {
-    fn dependencies(&self) -> HashMap<&String, HashSet<&String>> {
-        self.rules
-            .iter()
-            .map(|rule| (&rule.outer, rule.inner.keys().collect()))
-            .collect()
-    }
-
     fn reverse_dependencies(&self) -> HashMap<&String, HashSet<&String>> {
         let mut deps: HashMap<&String, HashSet<&String>> = HashMap::new();
         for rule in self.rules.iter() {
@@ -56,17 +53,6 @@ impl Ruleset {
         deps
     }
 
-    fn counts(&self) -> HashMap<(&String, &String), u8> {
-        self.rules
-            .iter()
-            .flat_map(|rule| {
-                rule.inner
-                    .iter()
-                    .map(move |(inner, &count)| ((&rule.outer, inner), count))
-            })
-            .collect()
-    }
-
     /// Find all possible types of bag that might contain `target` at any level of nesting.
     ///
     /// If the ruleset is treated as a dependency tree of outer bags to inner bags, then this
@@ -90,56 +76,197 @@ impl Ruleset {
         found
     }
 
-    fn count_contained_bags(&self, target: &String) -> u64 {
-        let all_deps = self.dependencies();
+    /// Find a shortest chain of bags from `outer` down to `inner`, where each bag directly
+    /// contains the next, including both ends. Only bags that have a rule can be `outer`.
+    ///
+    /// The chain always has at least one step, so a bag only contains itself through a cycle.
+    fn containment_path(&self, outer: &String, inner: &String) -> Option<Vec<&String>> {
+        let rules = self.by_outer();
+        let (&outer, _) = rules.get_key_value(outer)?;
+        let mut parents: HashMap<&String, &String> = HashMap::new();
+        let mut queue: VecDeque<&String> = VecDeque::from(vec![outer]);
+        while let Some(next) = queue.pop_front() {
+            for bag in rules.get(next).into_iter().flat_map(|r| r.inner.keys()) {
+                // Checked per edge rather than per bag, so `inner` can be `outer` itself
+                if bag == inner {
+                    let mut path = vec![bag, next];
+                    while let Some(&parent) = parents.get(path.last().unwrap()) {
+                        path.push(parent);
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                if bag != outer && !parents.contains_key(bag) {
+                    parents.insert(bag, next);
+                    queue.push_back(bag);
+                }
+            }
+        }
+        None
+    }
+
+    fn by_outer(&self) -> HashMap<&String, &Rule> {
+        self.rules.iter().map(|rule| (&rule.outer, rule)).collect()
+    }
 
-        let mut filtered_deps: HashMap<&String, HashSet<&String>> = HashMap::new();
-        let mut filter_queue: VecDeque<&String> = VecDeque::from(vec![target]);
-        while let Some(outer) = filter_queue.pop_front() {
-            let inners = all_deps[outer].clone();
-            filter_queue.extend(
-                inners
-                    .iter()
-                    .filter(|&&inner| !filtered_deps.contains_key(inner)),
-            );
-            filtered_deps.insert(outer, inners);
+    /// Find a cycle of bags that (indirectly) contain themselves, starting and ending with the same
+    /// bag, if there is one.
+    ///
+    /// Depth-first search from each bag in turn: reaching a bag that is already on the current path
+    /// means the path from that bag onwards is a cycle.
+    fn find_cycle(&self) -> Option<Vec<&String>> {
+        fn visit<'a>(
+            rules: &HashMap<&'a String, &'a Rule>,
+            bag: &'a String,
+            path: &mut Vec<&'a String>,
+            done: &mut HashSet<&'a String>,
+        ) -> Option<Vec<&'a String>> {
+            if let Some(start) = path.iter().position(|&b| b == bag) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(bag);
+                return Some(cycle);
+            }
+            if done.contains(bag) {
+                return None;
+            }
+            path.push(bag);
+            for inner in rules.get(bag).into_iter().flat_map(|r| r.inner.keys()) {
+                if let Some(cycle) = visit(rules, inner, path, done) {
+                    return Some(cycle);
+                }
+            }
+            path.pop();
+            done.insert(bag);
+            None
         }
 
-        let mut remaining_deps = filtered_deps.clone();
-
-        let mut visited: HashSet<&String> = HashSet::new();
-        let mut ordered: Vec<&String> = Vec::new();
-        while !remaining_deps.is_empty() {
-            let next: Vec<_> = remaining_deps
-                .iter()
-                .filter_map(|(&outer, inners)| {
-                    if inners.is_subset(&visited) {
-                        visited.insert(outer);
-                        ordered.push(outer);
-                        Some(outer)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            for k in next {
-                remaining_deps.remove(k);
+        let rules = self.by_outer();
+        let mut done = HashSet::new();
+        self.rules
+            .iter()
+            .find_map(|rule| visit(&rules, &rule.outer, &mut Vec::new(), &mut done))
+    }
+
+    /// Count how many bags are inside `target`, at any level of nesting.
+    ///
+    /// Each bag's total is memoised, so shared contents are only counted once. Counts multiply
+    /// along every level of nesting, so they are big integers. Only a cycle among the bags inside
+    /// `target` is an error, because then it would contain infinitely many bags.
+    fn count_contained_bags(&self, target: &String) -> crate::Result<BigUint> {
+        fn count<'a>(
+            rules: &HashMap<&'a String, &'a Rule>,
+            bag: &'a String,
+            memo: &mut HashMap<&'a String, BigUint>,
+            path: &mut Vec<&'a String>,
+        ) -> crate::Result<BigUint> {
+            if let Some(total) = memo.get(bag) {
+                return Ok(total.clone());
+            }
+            check_not_on_path(path, bag)?;
+            path.push(bag);
+            let mut total = BigUint::zero();
+            if let Some(rule) = rules.get(bag) {
+                for (inner, &n) in rule.inner.iter() {
+                    // Each inner bag, plus everything inside it
+                    total += (count(rules, inner, memo, path)? + BigUint::one()) * n;
+                }
             }
+            path.pop();
+            memo.insert(bag, total.clone());
+            Ok(total)
         }
 
-        let edge_counts = self.counts();
-        let mut counts: HashMap<&String, u64> = HashMap::new();
-        for outer in ordered {
-            let inner_count: u64 = filtered_deps[outer]
-                .iter()
-                .map(|&inner| counts[inner] * edge_counts[&(outer, inner)] as u64)
-                .sum();
-            // Add 1 to account for the `outer` bag too
-            counts.insert(outer, inner_count + 1);
+        count(
+            &self.by_outer(),
+            target,
+            &mut HashMap::new(),
+            &mut Vec::new(),
+        )
+    }
+
+    /// Find the longest chain of bags where each directly contains the next, preferring the bag
+    /// that comes first by name when chains are equally long.
+    fn deepest_chain(&self) -> crate::Result<Vec<&String>> {
+        fn depth<'a>(
+            rules: &HashMap<&'a String, &'a Rule>,
+            bag: &'a String,
+            memo: &mut HashMap<&'a String, (usize, Option<&'a String>)>,
+            path: &mut Vec<&'a String>,
+        ) -> crate::Result<usize> {
+            if let Some(&(d, _)) = memo.get(bag) {
+                return Ok(d);
+            }
+            check_not_on_path(path, bag)?;
+            path.push(bag);
+            let mut best = (1, None);
+            for inner in rules.get(bag).into_iter().flat_map(|r| r.inner.keys()) {
+                let d = depth(rules, inner, memo, path)? + 1;
+                if d > best.0 {
+                    best = (d, Some(inner));
+                }
+            }
+            path.pop();
+            memo.insert(bag, best);
+            Ok(best.0)
         }
 
-        // Subtract 1 because we're looking for the contents of `target`, not including `target`
-        counts.get(target).cloned().unwrap() - 1
+        let rules = self.by_outer();
+        let mut memo = HashMap::new();
+        let mut outers: Vec<&String> = rules.keys().cloned().collect();
+        outers.sort();
+        let mut best: Option<(usize, &String)> = None;
+        for outer in outers {
+            let d = depth(&rules, outer, &mut memo, &mut Vec::new())?;
+            if best.is_none_or(|(best_d, _)| d > best_d) {
+                best = Some((d, outer));
+            }
+        }
+        let mut chain = Vec::new();
+        let mut next = best.map(|(_, bag)| bag);
+        while let Some(bag) = next {
+            chain.push(bag);
+            next = memo[bag].1;
+        }
+        Ok(chain)
+    }
+
+    /// Write the rules as a Graphviz DOT digraph, with an edge from each bag to each bag it
+    /// contains, labelled with how many.
+    fn to_dot(&self) -> String {
+        let mut output = String::from("digraph bags {\n");
+        for rule in self.rules.iter() {
+            if rule.inner.is_empty() {
+                writeln!(output, "    {:?};", rule.outer).unwrap();
+            }
+            for (inner, count) in rule.inner.iter() {
+                writeln!(
+                    output,
+                    "    {:?} -> {:?} [label={}];",
+                    rule.outer, inner, count
+                )
+                .unwrap();
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+}
+
+fn format_cycle(cycle: &[&String]) -> String {
+    let names: Vec<&str> = cycle.iter().map(|s| s.as_str()).collect();
+    names.join(" -> ")
+}
+
+/// Error if `bag` is already on `path` during a depth-first search, because then the rules have a
+/// cycle and some bags would contain infinitely many bags.
+fn check_not_on_path(path: &[&String], bag: &String) -> crate::Result<()> {
+    match path.iter().position(|&b| b == bag) {
+        Some(start) => {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(bag);
+            Err(format!("cyclic rules: {}", format_cycle(&cycle)).into())
+        }
+        None => Ok(()),
     }
 }
 
@@ -158,13 +285,48 @@ fn part1(input_path: PathBuf) -> crate::Result<String> {
 
 fn part2(input_path: PathBuf) -> crate::Result<String> {
     let ruleset = read_input(&input_path)?;
-    let result = ruleset.count_contained_bags(&("shiny gold".to_owned()));
+    let result = ruleset.count_contained_bags(&("shiny gold".to_owned()))?;
     Ok(result.to_string())
 }
 
+fn deepest_chain(input_path: PathBuf) -> crate::Result<String> {
+    let ruleset = read_input(&input_path)?;
+    let chain = ruleset.deepest_chain()?;
+    let names: Vec<&str> = chain.iter().map(|s| s.as_str()).collect();
+    Ok(names.join(" > "))
+}
+
+/// Explain how `outer` comes to contain `inner`, as a chain of bags that each directly contain
+/// the next.
+pub fn containment_path(input_path: PathBuf, outer: &str, inner: &str) -> crate::Result<String> {
+    let ruleset = read_input(&input_path)?;
+    let path = ruleset
+        .containment_path(&outer.to_owned(), &inner.to_owned())
+        .ok_or_else(|| format!("{} bags can't contain {} bags", outer, inner))?;
+    let names: Vec<&str> = path.iter().map(|s| s.as_str()).collect();
+    Ok(names.join(" > "))
+}
+
+/// Describe a cycle of bags that contain themselves, if the rules have one.
+pub fn describe_cycle(input_path: PathBuf) -> crate::Result<String> {
+    let ruleset = read_input(&input_path)?;
+    Ok(match ruleset.find_cycle() {
+        Some(cycle) => format_cycle(&cycle),
+        None => "no cyclic rules".to_owned(),
+    })
+}
+
+/// Export the rules as a Graphviz DOT graph, e.g. for `dot -Tsvg`.
+pub fn export_dot(input_path: PathBuf) -> crate::Result<String> {
+    Ok(read_input(&input_path)?.to_dot())
+}
+
 pub fn register(runner: &mut crate::Runner) {
     runner.add("day07part1", || part1(data_path!("day07_input.txt")));
     runner.add("day07part2", || part2(data_path!("day07_input.txt")));
+    runner.add("day07deepest_chain", || {
+        deepest_chain(data_path!("day07_input.txt"))
+    });
 }
 
 #[cfg(test)]
@@ -190,4 +352,137 @@ mod tests {
     fn test_part2_solution() {
         assert_eq!(part2(data_path!("day07_input.txt")).unwrap(), "18925");
     }
+
+    fn parse_ruleset(lines: &[&str]) -> Ruleset {
+        Ruleset {
+            rules: lines.iter().map(|line| line.parse().unwrap()).collect(),
+        }
+    }
+
+    fn names(bags: &[&String]) -> Vec<String> {
+        bags.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_containment_path() {
+        let ruleset = read_input(&data_path!("day07_example.txt")).unwrap();
+        let path = ruleset
+            .containment_path(&"light red".into(), &"dotted black".into())
+            .unwrap();
+        assert_eq!(
+            names(&path),
+            vec![
+                "light red",
+                "bright white",
+                "shiny gold",
+                "dark olive",
+                "dotted black"
+            ]
+        );
+        assert!(ruleset
+            .containment_path(&"faded blue".into(), &"faded blue".into())
+            .is_none());
+        assert!(ruleset
+            .containment_path(&"light red".into(), &"light red".into())
+            .is_none());
+        assert!(ruleset
+            .containment_path(&"shiny gold".into(), &"light red".into())
+            .is_none());
+    }
+
+    #[test]
+    fn test_deepest_chain() {
+        let ruleset = read_input(&data_path!("day07_example.txt")).unwrap();
+        assert_eq!(
+            names(&ruleset.deepest_chain().unwrap()),
+            vec![
+                "dark orange",
+                "bright white",
+                "shiny gold",
+                "dark olive",
+                "dotted black"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycles() {
+        let ruleset = read_input(&data_path!("day07_example.txt")).unwrap();
+        assert!(ruleset.find_cycle().is_none());
+
+        let cyclic = parse_ruleset(&[
+            "light red bags contain 1 bright white bag.",
+            "bright white bags contain 2 muted yellow bags, 1 faded blue bag.",
+            "muted yellow bags contain 1 light red bag.",
+            "faded blue bags contain no other bags.",
+        ]);
+        assert_eq!(
+            names(&cyclic.find_cycle().unwrap()),
+            vec!["light red", "bright white", "muted yellow", "light red"]
+        );
+        let err = cyclic
+            .count_contained_bags(&"light red".into())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "cyclic rules: light red -> bright white -> muted yellow -> light red"
+        );
+        assert!(cyclic.deepest_chain().is_err());
+        // Bags that can't reach the cycle can still be counted
+        assert_eq!(
+            cyclic.count_contained_bags(&"faded blue".into()).unwrap(),
+            BigUint::zero()
+        );
+        let err = cyclic
+            .count_contained_bags(&"muted yellow".into())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "cyclic rules: muted yellow -> light red -> bright white -> muted yellow"
+        );
+        let path = cyclic
+            .containment_path(&"bright white".into(), &"bright white".into())
+            .unwrap();
+        assert_eq!(
+            names(&path),
+            vec!["bright white", "muted yellow", "light red", "bright white"]
+        );
+        assert!(cyclic
+            .containment_path(&"faded blue".into(), &"faded blue".into())
+            .is_none());
+    }
+
+    #[test]
+    fn test_large_counts() {
+        // 1000 bags each containing 1000 of the next, 10 levels deep
+        let lines: Vec<String> = (0..10)
+            .map(|i| format!("level{} bags contain 1000 level{} bags.", i, i + 1))
+            .chain(std::iter::once(
+                "level10 bags contain no other bags.".into(),
+            ))
+            .collect();
+        let lines: Vec<&str> = lines.iter().map(|s| s.as_str()).collect();
+        let ruleset = parse_ruleset(&lines);
+        let expected: BigUint = (1..=10).map(|i| BigUint::from(1000_u32).pow(i)).sum();
+        assert_eq!(
+            ruleset.count_contained_bags(&"level0".into()).unwrap(),
+            expected
+        );
+        assert!(expected > BigUint::from(u64::MAX));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let ruleset = parse_ruleset(&[
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.",
+            "bright white bags contain no other bags.",
+        ]);
+        assert_eq!(
+            ruleset.to_dot(),
+            "digraph bags {\n    \"light red\" -> \"bright white\" [label=1];\n    \
+             \"light red\" -> \"muted yellow\" [label=2];\n    \"bright white\";\n}\n"
+        );
+    }
 }
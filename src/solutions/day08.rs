@@ -1,10 +1,11 @@
-use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::str::FromStr;
 
 use super::prelude::*;
 use crate::util;
+use crate::vm::{Halt, Instruction, Machine, Registers, Word};
 
-type Word = i64;
+const ACC: &str = "acc";
 
 #[derive(Clone, Copy, Debug)]
 enum Op {
@@ -17,7 +18,7 @@ impl FromStr for Op {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (opcode, operand) = util::str_partition(&s, " ");
+        let (opcode, operand) = util::str_partition(s, " ");
         match opcode {
             "acc" => Ok(Op::Acc(operand.parse()?)),
             "jmp" => Ok(Op::Jmp(operand.parse()?)),
@@ -27,15 +28,30 @@ impl FromStr for Op {
     }
 }
 
-impl Op {
-    fn apply(&self, (pc, acc): (Word, Word)) -> (Word, Word) {
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Op::Acc(v) => (pc + 1, acc + v),
-            Op::Jmp(v) => (pc + v, acc),
-            Op::Nop(_) => (pc + 1, acc),
+            Op::Acc(v) => write!(f, "acc {:+}", v),
+            Op::Jmp(v) => write!(f, "jmp {:+}", v),
+            Op::Nop(v) => write!(f, "nop {:+}", v),
         }
     }
+}
 
+impl Instruction for Op {
+    fn execute(&self, registers: &mut Registers) -> Word {
+        match self {
+            Op::Acc(v) => {
+                registers.add(ACC, *v);
+                1
+            }
+            Op::Jmp(v) => *v,
+            Op::Nop(_) => 1,
+        }
+    }
+}
+
+impl Op {
     fn flip_jmp_nop(&self) -> Option<Self> {
         match self {
             Op::Jmp(v) => Some(Op::Nop(*v)),
@@ -47,142 +63,323 @@ impl Op {
 
 type Program = Vec<Op>;
 
-struct Machine {
-    program: Program,
-    pc: Word,
-    acc: Word,
+fn read_input(input_path: &PathBuf) -> crate::Result<Program> {
+    util::read_lines(input_path)
+        .map(|line| line.parse::<Op>())
+        .collect::<Result<Vec<_>, _>>()
 }
 
-impl Machine {
-    fn new(program: Program) -> Self {
-        Machine {
-            program,
-            pc: 0,
-            acc: 0,
-        }
+/// Part 1: "Immediately before any instruction is executed a second time, what value is in the
+/// accumulator?"
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    let mut machine = Machine::new(read_input(&input_path)?);
+    match machine.run() {
+        Halt::InfiniteLoop { .. } => Ok(machine.registers().get(ACC).to_string()),
+        halt => Err(format!("expected infinite loop, got {:?}", halt).into()),
     }
+}
 
-    fn get_state(&self) -> (Word, Word) {
-        (self.pc, self.acc)
+/// A single-instruction change that makes a program terminate, and why.
+#[derive(Clone, Debug)]
+struct Fix {
+    pc: usize,
+    original: Op,
+    replacement: Op,
+    reason: String,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "changed `{}` at {} to `{}`, because {}",
+            self.original, self.pc, self.replacement, self.reason
+        )
     }
+}
 
-    fn set_state(&mut self, (pc, acc): (Word, Word)) {
-        self.pc = pc;
-        self.acc = acc;
+impl Fix {
+    fn apply(&self, program: &mut Program) {
+        program[self.pc] = self.replacement;
     }
+}
 
-    fn current_op(&self) -> Option<&Op> {
-        if (self.pc as usize) < self.program.len() {
-            Some(&self.program[self.pc as usize])
-        } else {
-            None
+/// Find the fix by tracing the original run and trying to flip each executed instruction in turn,
+/// re-running the whole program each time.
+fn find_fix_brute_force(program: &Program) -> crate::Result<Fix> {
+    let mut machine = Machine::new(program.clone());
+    machine.enable_trace();
+    machine.run();
+    let executed: Vec<usize> = machine.trace().iter().map(|entry| entry.pc).collect();
+
+    for pc in executed {
+        let original = machine.program()[pc];
+        if let Some(replacement) = original.flip_jmp_nop() {
+            machine.program_mut()[pc] = replacement;
+            machine.reset();
+            if machine.run() == Halt::Terminated {
+                return Ok(Fix {
+                    pc,
+                    original,
+                    replacement,
+                    reason: "the program terminates when it is flipped".into(),
+                });
+            }
+            machine.program_mut()[pc] = original;
+        }
+    }
+    Err("no single jmp/nop flip makes the program terminate".into())
+}
+
+/// Control-flow graph of a program.
+///
+/// Node `i` is the instruction at `i`, and node `program.len()` represents normal termination.
+/// Control flow doesn't depend on the accumulator, so each instruction has exactly one successor,
+/// or none if it jumps out of bounds.
+struct ControlFlowGraph {
+    successors: Vec<Option<usize>>,
+}
+
+impl ControlFlowGraph {
+    fn new(program: &[Op]) -> Self {
+        ControlFlowGraph {
+            successors: (0..program.len())
+                .map(|pc| Self::successor(program, pc, &program[pc]))
+                .collect(),
         }
     }
 
-    fn current_op_mut(&mut self) -> Option<&mut Op> {
-        if (self.pc as usize) < self.program.len() {
-            Some(&mut self.program[self.pc as usize])
+    /// Where control goes after executing `op` at `pc`.
+    fn successor(program: &[Op], pc: usize, op: &Op) -> Option<usize> {
+        let offset = match op {
+            Op::Jmp(v) => *v,
+            Op::Acc(_) | Op::Nop(_) => 1,
+        };
+        let next = pc as Word + offset;
+        if (0..=program.len() as Word).contains(&next) {
+            Some(next as usize)
         } else {
             None
         }
     }
 
-    fn flip_jmp_nop(&mut self) -> bool {
-        if let Some(op) = self.current_op_mut() {
-            if let Some(new_op) = op.flip_jmp_nop() {
-                *op = new_op;
-                return true;
+    fn end(&self) -> usize {
+        self.successors.len()
+    }
+
+    /// Find every node that leads to normal termination, by searching backwards from the end.
+    fn reaches_end(&self) -> Vec<bool> {
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); self.end() + 1];
+        for (pc, next) in self.successors.iter().enumerate() {
+            if let Some(next) = next {
+                predecessors[*next].push(pc);
+            }
+        }
+        let mut reaches = vec![false; self.end() + 1];
+        reaches[self.end()] = true;
+        let mut stack = vec![self.end()];
+        while let Some(node) = stack.pop() {
+            for &pc in predecessors[node].iter() {
+                if !reaches[pc] {
+                    reaches[pc] = true;
+                    stack.push(pc);
+                }
             }
         }
-        false
+        reaches
     }
 
-    fn step(&mut self) -> Option<(Word, Word)> {
-        if let Some(op) = self.current_op() {
-            let new_state = op.apply(self.get_state());
-            self.set_state(new_state);
-            Some(new_state)
-        } else {
-            None
+    /// Find every instruction whose outgoing edge closes a loop, in a depth-first search that starts
+    /// from the first instruction and then from each instruction not yet visited.
+    fn back_edges(&self) -> Vec<bool> {
+        #[derive(Clone, Copy, Eq, PartialEq)]
+        enum State {
+            Unvisited,
+            OnPath,
+            Done,
+        }
+        let mut state = vec![State::Unvisited; self.end()];
+        let mut back_edges = vec![false; self.end()];
+        for start in 0..self.end() {
+            let mut path = Vec::new();
+            let mut pc = Some(start);
+            while let Some(node) = pc.filter(|&p| p < self.end() && state[p] == State::Unvisited) {
+                state[node] = State::OnPath;
+                path.push(node);
+                pc = self.successors[node];
+            }
+            if pc.is_some_and(|p| p < self.end() && state[p] == State::OnPath) {
+                back_edges[*path.last().unwrap()] = true;
+            }
+            for node in path {
+                state[node] = State::Done;
+            }
         }
+        back_edges
     }
-}
 
-impl Iterator for Machine {
-    type Item = (Word, Word);
+    /// Follow control flow from the first instruction until it leaves the program or repeats.
+    fn execution_path(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.end()];
+        let mut path = Vec::new();
+        let mut pc = Some(0);
+        while let Some(next) = pc.filter(|&p| p < self.end() && !visited[p]) {
+            visited[next] = true;
+            path.push(next);
+            pc = self.successors[next];
+        }
+        path
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.step()
+/// Find the fix in linear time using the control-flow graph.
+///
+/// The changed instruction must be on the original execution path, because nothing else is ever
+/// executed before the change takes effect. Flipping it only changes its own successor, so the
+/// fixed program terminates exactly when the new successor could already reach the end.
+fn find_fix_cfg(program: &Program) -> crate::Result<Fix> {
+    let cfg = ControlFlowGraph::new(program);
+    let reaches_end = cfg.reaches_end();
+    for pc in cfg.execution_path() {
+        let original = program[pc];
+        if let Some(replacement) = original.flip_jmp_nop() {
+            if let Some(next) = ControlFlowGraph::successor(program, pc, &replacement) {
+                if reaches_end[next] {
+                    return Ok(Fix {
+                        pc,
+                        original,
+                        replacement,
+                        reason: format!(
+                            "it is executed from the start, and after flipping it continues to {} \
+                             which leads to the end of the program",
+                            next
+                        ),
+                    });
+                }
+            }
+        }
     }
+    Err("no single jmp/nop flip makes the program terminate".into())
 }
 
-fn read_input(input_path: &PathBuf) -> crate::Result<Machine> {
-    let program = util::read_lines(input_path)
-        .map(|line| line.parse::<Op>())
-        .collect::<Result<Vec<_>, _>>()?;
-    Ok(Machine::new(program))
+/// Writes a program back out in the input format.
+struct Listing<'a>(&'a [Op]);
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in self.0.iter() {
+            writeln!(f, "{}", op)?;
+        }
+        Ok(())
+    }
 }
 
-fn part1(input_path: PathBuf) -> crate::Result<String> {
-    let mut machine = read_input(&input_path)?;
-    let mut visited: Vec<u8> = Vec::with_capacity(machine.program.len());
-    visited.resize(machine.program.len(), 0);
-    while visited[machine.pc as usize] == 0 {
-        visited[machine.pc as usize] += 1;
-        machine.step();
-    }
-    Ok(machine.acc.to_string())
-}
-
-fn part2(input_path: PathBuf) -> crate::Result<String> {
-    let mut machine = read_input(&input_path)?;
-    let initial_state = machine.get_state();
-
-    // Step 1: find the cycle
-    let mut history: Vec<(Word, Word)> = vec![initial_state];
-    let mut visited: HashMap<Word, usize> = HashMap::new();
-    let mut cycle_start: Option<usize> = None;
-    for (pc, acc) in std::iter::once(initial_state).chain(&mut machine) {
-        if let Some(pos) = visited.get(&pc).cloned() {
-            cycle_start = Some(pos);
-            break;
+/// Writes a program with jump targets as labels, and marks instructions that are never executed
+/// and jumps that close a loop.
+///
+/// ```text
+///          0  nop +0
+/// L1:      1  acc +1
+///          2  jmp -1   ; -> L1, loop back-edge
+///          3  acc +6   ; unreachable
+/// ```
+struct AnnotatedListing<'a>(&'a [Op]);
+
+impl AnnotatedListing<'_> {
+    fn label(&self, pc: usize) -> String {
+        if pc == self.0.len() {
+            "end".to_owned()
         } else {
-            visited.insert(pc, history.len());
-            history.push((pc, acc));
-        }
-    }
-    let cycle_history = &history[cycle_start.unwrap()..];
-
-    // Step 2: try to break the cycle by flipping 1 op at a time
-    'outer: for state in cycle_history {
-        machine.set_state(*state);
-        // Flip the op if it can be flipped
-        if machine.flip_jmp_nop() {
-            // Find out if we still have a cycle
-            // machine.set_state(initial_state);
-            let mut visited: HashSet<Word> = HashSet::new();
-            for (pc, _) in &mut machine {
-                if !visited.insert(pc) {
-                    // Flip it back so we can try with a different instruction
-                    machine.set_state(*state);
-                    machine.flip_jmp_nop();
-                    continue 'outer;
+            format!("L{}", pc)
+        }
+    }
+}
+
+impl fmt::Display for AnnotatedListing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let program = self.0;
+        let cfg = ControlFlowGraph::new(program);
+        let back_edges = cfg.back_edges();
+        let mut reachable = vec![false; program.len()];
+        for pc in cfg.execution_path() {
+            reachable[pc] = true;
+        }
+        let mut is_target = vec![false; program.len() + 1];
+        for (pc, op) in program.iter().enumerate() {
+            if let (Op::Jmp(_), Some(next)) = (op, cfg.successors[pc]) {
+                is_target[next] = true;
+            }
+        }
+
+        for (pc, op) in program.iter().enumerate() {
+            let mut notes = Vec::new();
+            if let Op::Jmp(_) = op {
+                match cfg.successors[pc] {
+                    Some(next) => notes.push(format!("-> {}", self.label(next))),
+                    None => notes.push("-> out of bounds".to_owned()),
                 }
             }
-            // If we got this far the program terminated, i.e. the currently flipped op is correct
-            break 'outer;
+            if back_edges[pc] {
+                notes.push("loop back-edge".to_owned());
+            }
+            if !reachable[pc] {
+                notes.push("unreachable".to_owned());
+            }
+
+            let label = if is_target[pc] {
+                format!("{}:", self.label(pc))
+            } else {
+                String::new()
+            };
+            let line = format!("{:<8}{:>2}  {}", label, pc, op);
+            if notes.is_empty() {
+                writeln!(f, "{}", line)?;
+            } else {
+                writeln!(f, "{:<20} ; {}", line, notes.join(", "))?;
+            }
+        }
+        if is_target[cfg.end()] {
+            writeln!(f, "{}:", self.label(cfg.end()))?;
         }
+        Ok(())
+    }
+}
+
+/// Disassemble a program, optionally annotated with control flow, e.g. to inspect corrupted boot
+/// code.
+pub fn disassemble(input_path: PathBuf, annotated: bool) -> crate::Result<String> {
+    let program = read_input(&input_path)?;
+    if annotated {
+        Ok(AnnotatedListing(&program).to_string())
+    } else {
+        Ok(Listing(&program).to_string())
     }
+}
 
-    // Step 3: run the modified program from the initial state to get the final state
-    machine.set_state(initial_state);
-    let (_, acc) = machine.last().unwrap();
-    Ok(acc.to_string())
+/// Part 2: "Fix the program so that it terminates normally by changing exactly one jmp (to nop) or
+/// nop (to jmp). What is the value of the accumulator after the program terminates?"
+fn part2<F: Fn(&Program) -> crate::Result<Fix>>(
+    input_path: PathBuf,
+    find_fix: F,
+) -> crate::Result<String> {
+    let mut program = read_input(&input_path)?;
+    let fix = find_fix(&program)?;
+    fix.apply(&mut program);
+    let mut machine = Machine::new(program);
+    match machine.run() {
+        Halt::Terminated => Ok(machine.registers().get(ACC).to_string()),
+        halt => Err(format!("fixed program didn't terminate: {:?}", halt).into()),
+    }
 }
 
 pub fn register(runner: &mut crate::Runner) {
     runner.add("day08part1", || part1(data_path!("day08_input.txt")));
-    runner.add("day08part2", || part2(data_path!("day08_input.txt")));
+    runner.add("day08part2_brute_force", || {
+        part2(data_path!("day08_input.txt"), find_fix_brute_force)
+    });
+    runner.add("day08part2_cfg", || {
+        part2(data_path!("day08_input.txt"), find_fix_cfg)
+    });
 }
 
 #[cfg(test)]
@@ -199,13 +396,91 @@ mod tests {
         assert_eq!(part1(data_path!("day08_input.txt")).unwrap(), "2014");
     }
 
+    #[test]
+    fn test_find_fix_cfg_example() {
+        let program = read_input(&data_path!("day08_example.txt")).unwrap();
+        let fix = find_fix_cfg(&program).unwrap();
+        assert_eq!(fix.pc, 7);
+        assert!(matches!(fix.original, Op::Jmp(-4)));
+        assert!(matches!(fix.replacement, Op::Nop(-4)));
+        assert_eq!(
+            fix.to_string(),
+            "changed `jmp -4` at 7 to `nop -4`, because it is executed from the start, and after \
+             flipping it continues to 8 which leads to the end of the program"
+        );
+    }
+
+    #[test]
+    fn test_control_flow_graph() {
+        let program: Program = vec![Op::Jmp(2), Op::Jmp(-5), Op::Nop(-1), Op::Jmp(-1)];
+        let cfg = ControlFlowGraph::new(&program);
+        assert_eq!(cfg.successors, vec![Some(2), None, Some(3), Some(2)]);
+        assert_eq!(cfg.execution_path(), vec![0, 2, 3]);
+        assert_eq!(cfg.reaches_end(), vec![false, false, false, false, true]);
+        let fix = find_fix_cfg(&program).unwrap();
+        assert_eq!(fix.pc, 3);
+    }
+
+    #[test]
+    fn test_listing_round_trip() {
+        let text = std::fs::read_to_string(data_path!("day08_example.txt")).unwrap();
+        assert_eq!(
+            disassemble(data_path!("day08_example.txt"), false).unwrap(),
+            text
+        );
+    }
+
+    #[test]
+    fn test_annotated_listing() {
+        let listing = disassemble(data_path!("day08_example.txt"), true).unwrap();
+        assert_eq!(
+            listing,
+            "         0  nop +0
+L1:      1  acc +1
+         2  jmp +4   ; -> L6
+L3:      3  acc +3
+         4  jmp -3   ; -> L1, loop back-edge
+         5  acc -99  ; unreachable
+L6:      6  acc +1
+         7  jmp -4   ; -> L3
+         8  acc +6   ; unreachable
+"
+        );
+
+        let program: Program = vec![Op::Jmp(2), Op::Jmp(-5), Op::Nop(0), Op::Jmp(-1), Op::Jmp(1)];
+        assert_eq!(
+            AnnotatedListing(&program).to_string(),
+            "         0  jmp +2   ; -> L2
+         1  jmp -5   ; -> out of bounds, unreachable
+L2:      2  nop +0
+         3  jmp -1   ; -> L2, loop back-edge
+         4  jmp +1   ; -> end, unreachable
+end:
+"
+        );
+    }
+
     #[test]
     fn test_part2_example() {
-        assert_eq!(part2(data_path!("day08_example.txt")).unwrap(), "8");
+        assert_eq!(
+            part2(data_path!("day08_example.txt"), find_fix_brute_force).unwrap(),
+            "8"
+        );
+        assert_eq!(
+            part2(data_path!("day08_example.txt"), find_fix_cfg).unwrap(),
+            "8"
+        );
     }
 
     #[test]
     fn test_part2_solution() {
-        assert_eq!(part2(data_path!("day08_input.txt")).unwrap(), "2251");
+        assert_eq!(
+            part2(data_path!("day08_input.txt"), find_fix_brute_force).unwrap(),
+            "2251"
+        );
+        assert_eq!(
+            part2(data_path!("day08_input.txt"), find_fix_cfg).unwrap(),
+            "2251"
+        );
     }
 }
@@ -1,40 +1,26 @@
+use std::collections::{HashMap, VecDeque};
+
 use super::prelude::*;
-use crate::util;
 
 struct Cypher {
     /// How many previous numbers are considered during validating a new number.
     window_size: usize,
-    /// The next cell/chunk to overwrite in the circular buffers.
-    next_index: usize,
-    /// Circular buffer of the most recent numbers.
-    recent: Vec<u64>,
-    /// Circular buffer of all valid next numbers.
-    ///
-    /// Size is `window_size.pow(2)`. This is actually ~2x the size it needs to be: addition is
-    /// commutative, so the number of valid next numbers is the nth triangular number (no need to
-    /// have both `a + b` and `b + a`), however having a nicely divisible array makes rolling
-    /// updates to a circular buffer much easier to calculate.
-    ///
-    /// Uses `Option<u64>` instead of just `u64` so that the "diagonal" can be marked as invalid.
-    valid: Vec<Option<u64>>,
+    /// The most recent numbers, oldest first.
+    recent: VecDeque<u64>,
+    /// Multiset of the sums of every pair of different entries in `recent`, as a count of how many
+    /// pairs give each sum, so that validating a number is a single lookup. Two `u64`s can sum to
+    /// more than a `u64`, so sums are `u128`.
+    sums: HashMap<u128, usize>,
 }
 
 impl Cypher {
     fn new(preamble: &[u64]) -> Self {
         assert!(preamble.len() >= 2);
 
-        let window_size = preamble.len();
-        let mut recent = Vec::with_capacity(window_size);
-        recent.resize(window_size, 0);
-        // The "diagonal" is invalid, because the number summed have to be different numbers
-        let valid = (0..window_size)
-            .flat_map(|i| (0..window_size).map(move |j| if i == j { None } else { Some(0) }))
-            .collect();
         let mut cypher = Cypher {
-            window_size,
-            next_index: 0,
-            recent,
-            valid,
+            window_size: preamble.len(),
+            recent: VecDeque::with_capacity(preamble.len()),
+            sums: HashMap::new(),
         };
         for next in preamble.iter().cloned() {
             cypher.update(next);
@@ -43,34 +29,25 @@ impl Cypher {
     }
 
     fn update(&mut self, next: u64) {
-        let i = self.next_index;
-
-        // Swap in the new number at the next position in the circular buffer of recent numbers
-        let old = self.recent[self.next_index];
-        self.recent[self.next_index] = next;
-
-        // Update the contiguous chunk of valid numbers that should include this number
-        for cell in &mut self.valid[(i * self.window_size)..((i + 1) * self.window_size)] {
-            if let Some(x) = cell {
-                *x -= old;
-                *x += next;
+        if self.recent.len() == self.window_size {
+            let old = self.recent.pop_front().unwrap();
+            for other in self.recent.iter() {
+                let sum = old as u128 + *other as u128;
+                let count = self.sums.get_mut(&sum).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.sums.remove(&sum);
+                }
             }
         }
-
-        // Update the numbers that are in the other chunks
-        for chunk in self.valid.chunks_mut(self.window_size) {
-            if let Some(x) = &mut chunk[i] {
-                *x -= old;
-                *x += next;
-            }
+        for other in self.recent.iter() {
+            *self.sums.entry(next as u128 + *other as u128).or_insert(0) += 1;
         }
-
-        // Update next index to use in the circular buffers
-        self.next_index = (i + 1) % self.window_size;
+        self.recent.push_back(next);
     }
 
     fn is_valid(&self, next: u64) -> bool {
-        self.valid.contains(&Some(next))
+        self.sums.contains_key(&(next as u128))
     }
 
     fn accept(&mut self, next: u64) -> bool {
@@ -80,16 +57,36 @@ impl Cypher {
     }
 }
 
+/// Parse one number per line from `reader`, lazily.
+fn parse_numbers<R: BufRead>(reader: R) -> impl Iterator<Item = crate::Result<u64>> {
+    reader.lines().map(|line| Ok(line?.parse::<u64>()?))
+}
+
 fn read_input(input_path: &PathBuf) -> crate::Result<Vec<u64>> {
-    util::read_lines(input_path)
-        .map(|line| line.parse::<u64>())
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(crate::Error::from)
+    let file = File::open(input_path)?;
+    parse_numbers(io::BufReader::new(file)).collect()
 }
 
-fn part1_impl(data: &[u64], window_size: usize) -> crate::Result<u64> {
-    let mut cypher = Cypher::new(&data[..window_size]);
-    for next in data[window_size..].iter().cloned() {
+/// Find the first number that isn't the sum of two of the `window_size` numbers before it,
+/// consuming `numbers` only as far as that number.
+fn find_invalid<I>(numbers: I, window_size: usize) -> crate::Result<u64>
+where
+    I: IntoIterator<Item = crate::Result<u64>>,
+{
+    if window_size < 2 {
+        return Err(format!("window size must be at least 2, got {}", window_size).into());
+    }
+    let mut numbers = numbers.into_iter();
+    let preamble = numbers
+        .by_ref()
+        .take(window_size)
+        .collect::<crate::Result<Vec<_>>>()?;
+    if preamble.len() < window_size {
+        return Err("input is shorter than the preamble".into());
+    }
+    let mut cypher = Cypher::new(&preamble);
+    for next in numbers {
+        let next = next?;
         if !cypher.accept(next) {
             return Ok(next);
         }
@@ -97,28 +94,63 @@ fn part1_impl(data: &[u64], window_size: usize) -> crate::Result<u64> {
     Err("no invalid numbers found".into())
 }
 
+/// Find the first invalid number in a stream of lines, e.g. from `io::stdin().lock()`.
+pub fn find_invalid_in<R: BufRead>(reader: R, window_size: usize) -> crate::Result<u64> {
+    find_invalid(parse_numbers(reader), window_size)
+}
+
+fn part1_impl(data: &[u64], window_size: usize) -> crate::Result<u64> {
+    find_invalid(data.iter().cloned().map(Ok), window_size)
+}
+
+/// Part 1: "What is the first number that does not have this property?"
+///
+/// Only reads as much of the input as it needs to.
 fn part1(input_path: PathBuf) -> crate::Result<String> {
-    let data = read_input(&input_path)?;
-    part1_impl(&data, 25).map(|result| result.to_string())
+    let file = File::open(input_path)?;
+    find_invalid_in(io::BufReader::new(file), 25).map(|result| result.to_string())
 }
 
-fn part2_impl(data: &[u64], window_size: usize) -> crate::Result<u64> {
-    let invalid = part1_impl(data, window_size)?;
-    let (mut start, mut end) = (0_usize, 1_usize);
-    let mut sum = data[start];
-    while sum != invalid {
-        if sum < invalid {
-            sum += data[end];
-            end += 1;
-        } else {
-            sum -= data[start];
+/// Find a contiguous range of at least two numbers that sums to `target`.
+///
+/// All numbers are non-negative, so a sliding window works: for each end of the range, move the
+/// start forward only while the sum is too large. That gives the longest range ending there with a
+/// small enough sum, so a range of length 1 is only settled for if no longer range exists, and the
+/// range may include `target` itself if it's next to zeros.
+fn find_contiguous_sum(data: &[u64], target: u64) -> crate::Result<&[u64]> {
+    let target = target as u128;
+    let mut start = 0;
+    // Never more than `target` plus one number, so can't overflow
+    let mut sum: u128 = 0;
+    for (end, &x) in data.iter().enumerate() {
+        sum += x as u128;
+        while sum > target && start < end {
+            sum -= data[start] as u128;
             start += 1;
         }
+        if sum == target && end > start {
+            return Ok(&data[start..=end]);
+        }
     }
-    let sum_data = &data[start..end];
-    Ok(sum_data.iter().min().unwrap() + sum_data.iter().max().unwrap())
+    Err(format!(
+        "no contiguous range of at least two numbers sums to {}",
+        target
+    )
+    .into())
 }
 
+fn part2_impl(data: &[u64], window_size: usize) -> crate::Result<u64> {
+    let invalid = part1_impl(data, window_size)?;
+    let sum_data = find_contiguous_sum(data, invalid)?;
+    let (min, max) = (
+        sum_data.iter().min().unwrap(),
+        sum_data.iter().max().unwrap(),
+    );
+    min.checked_add(*max)
+        .ok_or_else(|| format!("encryption weakness {} + {} overflows", min, max).into())
+}
+
+/// Part 2: "What is the encryption weakness in your XMAS-encrypted list of numbers?"
 fn part2(input_path: PathBuf) -> crate::Result<String> {
     let data = read_input(&input_path)?;
     part2_impl(&data, 25).map(|result| result.to_string())
@@ -139,6 +171,50 @@ mod tests {
         assert_eq!(part1_impl(&data, 5).unwrap(), 127);
     }
 
+    #[test]
+    fn test_cypher_duplicates() {
+        // The two numbers must be different entries, but can have the same value
+        let mut cypher = Cypher::new(&[1, 2, 2]);
+        assert!(cypher.is_valid(4));
+        assert!(!cypher.is_valid(2));
+        assert!(cypher.accept(3));
+        // 1 has dropped out of the window, leaving only [2, 2, 3]
+        assert!(!cypher.is_valid(3));
+        assert!(cypher.is_valid(5));
+    }
+
+    #[test]
+    fn test_find_invalid_in_stream() {
+        let text = std::fs::read_to_string(data_path!("day09_example1.txt")).unwrap();
+        assert_eq!(find_invalid_in(io::Cursor::new(text), 5).unwrap(), 127);
+        // Stops reading at the invalid number, so later bad input is never seen
+        let input = "1\n2\n3\n10\nnot a number\n";
+        assert_eq!(find_invalid_in(io::Cursor::new(input), 2).unwrap(), 10);
+        assert!(find_invalid_in(io::Cursor::new("1\n2\n3\n"), 5).is_err());
+        assert!(find_invalid_in(io::Cursor::new("1\n2\n3\n"), 1).is_err());
+        assert!(find_invalid_in(io::Cursor::new("1\n2\n3\n"), 0).is_err());
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let max = u64::MAX;
+        let data = [max, max - 1, 1, max, max - 2];
+        // max = (max - 1) + 1, but max - 2 isn't the sum of any two of [max - 1, 1, max]
+        assert_eq!(part1_impl(&data, 3).unwrap(), max - 2);
+        let err = find_contiguous_sum(&data, max - 2).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "no contiguous range of at least two numbers sums to {}",
+                max - 2
+            )
+        );
+        assert_eq!(
+            find_contiguous_sum(&[max - 2, 1, 1], max).unwrap(),
+            &[max - 2, 1, 1]
+        );
+    }
+
     #[test]
     fn test_part1_solution() {
         assert_eq!(part1(data_path!("day09_input.txt")).unwrap(), "133015568");
@@ -150,6 +226,21 @@ mod tests {
         assert_eq!(part2_impl(&data, 5).unwrap(), 62);
     }
 
+    #[test]
+    fn test_part2_impl_range_including_invalid() {
+        // 11 is invalid, and the only range that sums to it is [11, 0]
+        assert_eq!(part2_impl(&[2, 2, 4, 6, 11, 0], 2).unwrap(), 11);
+    }
+
+    #[test]
+    fn test_part2_impl_no_range() {
+        let err = part2_impl(&[1, 2, 3, 10], 2).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "no contiguous range of at least two numbers sums to 10"
+        );
+    }
+
     #[test]
     fn test_part2_solution() {
         assert_eq!(part2(data_path!("day09_input.txt")).unwrap(), "16107959");
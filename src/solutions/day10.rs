@@ -1,168 +1,200 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use num::{BigUint, One, Zero};
+
 use super::prelude::*;
 use crate::util;
 
-fn read_input(input_path: &PathBuf) -> crate::Result<Vec<u8>> {
+type Joltage = u32;
+
+fn read_input(input_path: &PathBuf) -> crate::Result<Vec<Joltage>> {
     util::read_lines(input_path)
-        .map(|line| line.parse::<u8>())
+        .map(|line| line.parse::<Joltage>())
         .collect::<Result<Vec<_>, _>>()
         .map_err(crate::Error::from)
 }
 
-/// From a slice of "joltage" values, find an order to connect them in, and return how many 1-jolt
-/// and 3-jolt steps there are in the chain as a tuple.
-///
-/// Given the constraints that `x[i+1] - x` must be in the range `1..=3`, the ordering is found by a
-/// simple sort. Then the connections can be processed with a pairwise iteration over the sorted
-/// data.
-fn part1_impl(data: &[u8]) -> crate::Result<(usize, usize)> {
-    let mut data = Vec::from(data);
-    data.sort();
-    let mut counts = vec![0_usize; 4];
-    let outlet = 0_u8;
-    let device = data.last().unwrap() + 3;
-    // Pairwise iteration over the endpoints = iteration over the connections
-    let prev_iter = std::iter::once(outlet).chain(data[..].iter().cloned());
-    let next_iter = data[..].iter().cloned().chain(std::iter::once(device));
-    for (a, b) in prev_iter.zip(next_iter) {
-        let diff = b - a;
-        if diff <= 3 {
-            counts[diff as usize] += 1;
-        } else {
-            return Err(format!("gap too large: {} -> {}", a, b).into());
+/// Rules for connecting adapters together.
+#[derive(Clone, Debug)]
+struct ChainRules {
+    /// Allowed differences between connected joltages, sorted and without duplicates.
+    gaps: Vec<Joltage>,
+    /// How much higher the device is rated than the highest adapter.
+    device_offset: Joltage,
+}
+
+impl ChainRules {
+    fn new(gaps: &[Joltage], device_offset: Joltage) -> crate::Result<Self> {
+        let mut gaps = gaps.to_vec();
+        gaps.sort_unstable();
+        gaps.dedup();
+        if gaps.first().is_none_or(|&gap| gap == 0) {
+            return Err(crate::Error::Parse(
+                format!("gaps must be positive, got {:?}", gaps).into(),
+            ));
+        }
+        if !gaps.contains(&device_offset) {
+            return Err(crate::Error::Parse(
+                format!(
+                    "device offset {} must be one of the allowed gaps {:?}",
+                    device_offset, gaps
+                )
+                .into(),
+            ));
         }
+        Ok(ChainRules {
+            gaps,
+            device_offset,
+        })
+    }
+
+    /// The rules from the puzzle: "Any given adapter can take an input 1, 2, or 3 jolts lower than
+    /// its rating", and the device is rated "3 jolts higher than the highest-rated adapter".
+    fn puzzle() -> crate::Result<Self> {
+        ChainRules::new(&[1, 2, 3], 3)
+    }
+
+    fn allows(&self, gap: Joltage) -> bool {
+        self.gaps.binary_search(&gap).is_ok()
+    }
+
+    fn max_gap(&self) -> Joltage {
+        *self.gaps.last().unwrap()
     }
-    Ok((counts[1], counts[3]))
 }
 
-fn part1(input_path: PathBuf) -> crate::Result<String> {
-    let data = read_input(&input_path)?;
-    let result = part1_impl(&data)?;
-    Ok((result.0 * result.1).to_string())
+/// A connection in an adapter chain that breaks the rules.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct InvalidGap {
+    /// Index of the connection, where 0 is the connection from the outlet to the first adapter.
+    position: usize,
+    from: Joltage,
+    to: Joltage,
 }
 
-/// Break up a device chain into chunks where mutation is possible.
-///
-/// - If the difference between a pair is 3, e.g. `(3, 6)` in `[1, 2, 3, 6, 7, 8]`, then neither of
-///   those elements can be removed and the permutation calculation can be performed on chunks
-///   either side of that connection, e.g. `[1, 2, 3]` and `[6, 7, 8]`.
-/// - If a chunk is smaller than 3 elements, there is no scope for removal, because the ends of
-///   a chunk are immutable by the previous definition.
-fn find_mutable_chunks(data: &[u8]) -> Vec<&[u8]> {
-    let mut output = Vec::new();
-    let mut chunk_start: usize = 0;
-    for (i, pair) in data.windows(2).enumerate() {
-        if pair[1] - pair[0] == 3 {
-            let chunk_boundary = i + 1;
-            let chunk = &data[chunk_start..chunk_boundary];
-            if chunk.len() > 2 {
-                output.push(chunk);
-            }
-            chunk_start = chunk_boundary;
-        }
+impl fmt::Display for InvalidGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gap of {} from {} to {} at position {} is not allowed",
+            self.to - self.from,
+            self.from,
+            self.to,
+            self.position
+        )
     }
-    output
 }
 
-fn is_chunk_valid<I: Iterator<Item = u8>>(mut iter: I) -> bool {
-    let (mut maybe_a, mut maybe_b) = (iter.next(), iter.next());
-    if maybe_a.is_none() || maybe_b.is_none() {
-        return false;
-    }
-    while let (Some(a), Some(b)) = (maybe_a, maybe_b) {
-        if b - a > 3 {
-            return false;
-        }
-        maybe_a = maybe_b;
-        maybe_b = iter.next();
+impl std::error::Error for InvalidGap {}
+
+impl From<InvalidGap> for crate::Error {
+    fn from(err: InvalidGap) -> Self {
+        crate::Error::Parse(Box::new(err))
     }
-    return true;
 }
 
-struct BinaryCounter {
-    bits: Vec<bool>,
-    done: bool,
+/// Every adapter connected in order, from the outlet to the device.
+struct AdapterChain {
+    /// Sorted joltages, starting with the outlet and ending with the device.
+    joltages: Vec<Joltage>,
+    rules: ChainRules,
 }
 
-impl BinaryCounter {
-    fn new(size: usize) -> Self {
-        assert!(size > 0);
-        let mut bits = Vec::with_capacity(size);
-        bits.resize(size, false);
-        BinaryCounter { bits, done: false }
+impl AdapterChain {
+    /// Connect all of `adapters` between the outlet and the device.
+    ///
+    /// Joltages can only increase along the chain, so the only possible order is found by a simple
+    /// sort, and then each connection is checked against `rules`.
+    ///
+    /// Fails with an `InvalidGap` for a connection that breaks the rules, or if the device's rating
+    /// is too high to represent.
+    fn new(adapters: &[Joltage], rules: &ChainRules) -> crate::Result<Self> {
+        let mut joltages = Vec::with_capacity(adapters.len() + 2);
+        // Power outlet
+        joltages.push(0);
+        joltages.extend_from_slice(adapters);
+        joltages.sort_unstable();
+        // Device
+        let highest = *joltages.last().unwrap();
+        let device = highest.checked_add(rules.device_offset).ok_or_else(|| {
+            crate::Error::Parse(
+                format!(
+                    "device rating {} + {} is too high",
+                    highest, rules.device_offset
+                )
+                .into(),
+            )
+        })?;
+        joltages.push(device);
+
+        for (position, pair) in joltages.windows(2).enumerate() {
+            if !rules.allows(pair[1] - pair[0]) {
+                return Err(InvalidGap {
+                    position,
+                    from: pair[0],
+                    to: pair[1],
+                }
+                .into());
+            }
+        }
+        Ok(AdapterChain {
+            joltages,
+            rules: rules.clone(),
+        })
     }
-}
 
-impl Iterator for BinaryCounter {
-    type Item = Vec<bool>;
+    /// Count how many connections there are with each gap.
+    fn gap_histogram(&self) -> BTreeMap<Joltage, usize> {
+        let mut histogram = BTreeMap::new();
+        for pair in self.joltages.windows(2) {
+            *histogram.entry(pair[1] - pair[0]).or_insert(0) += 1;
+        }
+        histogram
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let output = if self.done {
-            None
-        } else {
-            Some(self.bits.clone())
-        };
-        if !self.done {
-            let mut overflowed = true;
-            for i in (0..self.bits.len()).rev() {
-                self.bits[i] = !self.bits[i];
-                if self.bits[i] {
-                    overflowed = false;
+    /// Count the ways to connect the outlet to the device using some subset of the adapters.
+    ///
+    /// The number of ways to reach adapter `i` is the sum of the ways to reach each earlier adapter
+    /// that is an allowed gap below it, so with distinct joltages this is a single pass looking
+    /// back at most the largest gap (a tribonacci-style recurrence for the puzzle's rules).
+    ///
+    /// Counts grow exponentially with the length of the chain, so they are big integers.
+    fn count_arrangements(&self) -> BigUint {
+        let chain = &self.joltages;
+        let mut ways: Vec<BigUint> = Vec::with_capacity(chain.len());
+        ways.push(BigUint::one());
+        for (i, &joltage) in chain.iter().enumerate().skip(1) {
+            let mut total = BigUint::zero();
+            for j in (0..i).rev() {
+                let gap = joltage - chain[j];
+                if gap > self.rules.max_gap() {
                     break;
                 }
+                if self.rules.allows(gap) {
+                    total += &ways[j];
+                }
             }
-            self.done = overflowed;
+            ways.push(total);
         }
-        output
+        ways.pop().unwrap()
     }
 }
 
-/// Given `data` where no gap between values is larger than 2, find how many permutations are valid.
-///
-/// The only permutation allowed is removal of chargers, because of the requirement that chargers
-/// plug into other power sources that are 1-3 lower - a swap would violate that. Any combination of
-/// chargers can be removed as long as they are not the first or last (those are already on an
-/// immutable boundary) and as long as all pair differences remain in the range `1..=3`.
-fn count_chunk_permutations(data: &[u8]) -> usize {
-    // Iterate over possible keep/skip combinations for all chargers except the first and last
-    BinaryCounter::new(data.len() - 2)
-        .map(|mask| {
-            // Build the full mask, where first and last are mandatory (already part of a 3-gap)
-            std::iter::once(true)
-                .chain(mask.iter().cloned())
-                .chain(std::iter::once(true))
-                // Combine with data
-                .zip(data.iter())
-                // Turn into an iterator that only yields allowed items
-                .filter_map(|(allow, item)| if allow { Some(item) } else { None })
-                // ... and collect that into a sequence we can test
-                .cloned()
-                .collect::<Vec<_>>()
-        })
-        // Only allow valid sequences
-        .filter(|chunk| is_chunk_valid(chunk.iter().cloned()))
-        // How many sequences were valid?
-        .count()
+/// Part 1: "What is the number of 1-jolt differences multiplied by the number of 3-jolt
+/// differences?"
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    let chain = AdapterChain::new(&read_input(&input_path)?, &ChainRules::puzzle()?)?;
+    let histogram = chain.gap_histogram();
+    let count = |gap| histogram.get(&gap).cloned().unwrap_or(0);
+    Ok((count(1) * count(3)).to_string())
 }
 
+/// Part 2: "What is the total number of distinct ways you can arrange the adapters to connect the
+/// charging outlet to your device?"
 fn part2(input_path: PathBuf) -> crate::Result<String> {
-    // Power outlet
-    let mut data = vec![0_u8];
-    // Chargers
-    data.append(&mut read_input(&input_path)?);
-    // Sorted, as in part1
-    data.sort();
-    // Device
-    data.push(data.last().unwrap() + 3);
-
-    // Break the chain into smaller problems separated by gaps of 3
-    let chunks = find_mutable_chunks(&data);
-    // Total permutations = product of permutations of each "independent" chunk
-    Ok(chunks
-        .iter()
-        .cloned()
-        .map(count_chunk_permutations)
-        .product::<usize>()
-        .to_string())
+    let chain = AdapterChain::new(&read_input(&input_path)?, &ChainRules::puzzle()?)?;
+    Ok(chain.count_arrangements().to_string())
 }
 
 pub fn register(runner: &mut crate::Runner) {
@@ -174,16 +206,138 @@ pub fn register(runner: &mut crate::Runner) {
 mod tests {
     use super::*;
 
+    /// Break up a device chain into chunks where mutation is possible.
+    ///
+    /// - If the difference between a pair is 3, e.g. `(3, 6)` in `[1, 2, 3, 6, 7, 8]`, then neither
+    ///   of those elements can be removed and the permutation calculation can be performed on
+    ///   chunks either side of that connection, e.g. `[1, 2, 3]` and `[6, 7, 8]`.
+    /// - If a chunk is smaller than 3 elements, there is no scope for removal, because the ends of
+    ///   a chunk are immutable by the previous definition.
+    fn find_mutable_chunks(data: &[Joltage]) -> Vec<&[Joltage]> {
+        let mut output = Vec::new();
+        let mut chunk_start: usize = 0;
+        for (i, pair) in data.windows(2).enumerate() {
+            if pair[1] - pair[0] == 3 {
+                let chunk_boundary = i + 1;
+                let chunk = &data[chunk_start..chunk_boundary];
+                if chunk.len() > 2 {
+                    output.push(chunk);
+                }
+                chunk_start = chunk_boundary;
+            }
+        }
+        output
+    }
+
+    fn is_chunk_valid<I: Iterator<Item = Joltage>>(mut iter: I) -> bool {
+        let (mut maybe_a, mut maybe_b) = (iter.next(), iter.next());
+        if maybe_a.is_none() || maybe_b.is_none() {
+            return false;
+        }
+        while let (Some(a), Some(b)) = (maybe_a, maybe_b) {
+            if b - a > 3 {
+                return false;
+            }
+            maybe_a = maybe_b;
+            maybe_b = iter.next();
+        }
+        return true;
+    }
+
+    struct BinaryCounter {
+        bits: Vec<bool>,
+        done: bool,
+    }
+
+    impl BinaryCounter {
+        fn new(size: usize) -> Self {
+            assert!(size > 0);
+            let mut bits = Vec::with_capacity(size);
+            bits.resize(size, false);
+            BinaryCounter { bits, done: false }
+        }
+    }
+
+    impl Iterator for BinaryCounter {
+        type Item = Vec<bool>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let output = if self.done {
+                None
+            } else {
+                Some(self.bits.clone())
+            };
+            if !self.done {
+                let mut overflowed = true;
+                for i in (0..self.bits.len()).rev() {
+                    self.bits[i] = !self.bits[i];
+                    if self.bits[i] {
+                        overflowed = false;
+                        break;
+                    }
+                }
+                self.done = overflowed;
+            }
+            output
+        }
+    }
+
+    /// Given `data` where no gap between values is larger than 2, find how many permutations are
+    /// valid.
+    ///
+    /// The only permutation allowed is removal of chargers, because of the requirement that
+    /// chargers plug into other power sources that are 1-3 lower - a swap would violate that. Any
+    /// combination of chargers can be removed as long as they are not the first or last (those are
+    /// already on an immutable boundary) and as long as all pair differences remain in the range
+    /// `1..=3`.
+    fn count_chunk_permutations(data: &[Joltage]) -> usize {
+        // Iterate over possible keep/skip combinations for all chargers except the first and last
+        BinaryCounter::new(data.len() - 2)
+            .map(|mask| {
+                // Build the full mask, where first and last are mandatory (already part of a 3-gap)
+                std::iter::once(true)
+                    .chain(mask.iter().cloned())
+                    .chain(std::iter::once(true))
+                    // Combine with data
+                    .zip(data.iter())
+                    // Turn into an iterator that only yields allowed items
+                    .filter_map(|(allow, item)| if allow { Some(item) } else { None })
+                    // ... and collect that into a sequence we can test
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            // Only allow valid sequences
+            .filter(|chunk| is_chunk_valid(chunk.iter().cloned()))
+            // How many sequences were valid?
+            .count()
+    }
+
+    /// Count arrangements by brute force over the mutable chunks, as a check on
+    /// `count_arrangements()`.
+    fn count_arrangements_brute_force(chain: &[Joltage]) -> usize {
+        find_mutable_chunks(chain)
+            .iter()
+            .cloned()
+            .map(count_chunk_permutations)
+            .product()
+    }
+
+    fn histogram(pairs: &[(Joltage, usize)]) -> BTreeMap<Joltage, usize> {
+        pairs.iter().cloned().collect()
+    }
+
     #[test]
-    fn test_part1_impl_example1() {
+    fn test_gap_histogram_example1() {
         let data = read_input(&data_path!("day10_example1.txt")).unwrap();
-        assert_eq!(part1_impl(&data).unwrap(), (7, 5));
+        let chain = AdapterChain::new(&data, &ChainRules::puzzle().unwrap()).unwrap();
+        assert_eq!(chain.gap_histogram(), histogram(&[(1, 7), (3, 5)]));
     }
 
     #[test]
-    fn test_part1_impl_example2() {
+    fn test_gap_histogram_example2() {
         let data = read_input(&data_path!("day10_example2.txt")).unwrap();
-        assert_eq!(part1_impl(&data).unwrap(), (22, 10));
+        let chain = AdapterChain::new(&data, &ChainRules::puzzle().unwrap()).unwrap();
+        assert_eq!(chain.gap_histogram(), histogram(&[(1, 22), (3, 10)]));
     }
 
     #[test]
@@ -191,6 +345,75 @@ mod tests {
         assert_eq!(part1(data_path!("day10_input.txt")).unwrap(), "2312");
     }
 
+    #[test]
+    fn test_custom_rules() {
+        // Ratings larger than a byte, and gaps that aren't a contiguous range
+        let rules = ChainRules::new(&[10, 100, 5], 10).unwrap();
+        let chain = AdapterChain::new(&[125, 5, 225, 115, 15], &rules).unwrap();
+        assert_eq!(chain.joltages, vec![0, 5, 15, 115, 125, 225, 235]);
+        assert_eq!(
+            chain.gap_histogram(),
+            histogram(&[(5, 1), (10, 3), (100, 2)])
+        );
+    }
+
+    #[test]
+    fn test_invalid_gap() {
+        let err = match AdapterChain::new(&[1, 2, 6, 7], &ChainRules::puzzle().unwrap()) {
+            Err(crate::Error::Parse(err)) => err.downcast::<InvalidGap>().unwrap(),
+            other => panic!("expected invalid gap, got {:?}", other.map(|_| ())),
+        };
+        assert_eq!(
+            *err,
+            InvalidGap {
+                position: 2,
+                from: 2,
+                to: 6
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "gap of 4 from 2 to 6 at position 2 is not allowed"
+        );
+        // Gaps that are small enough still have to be in the allowed set
+        let rules = ChainRules::new(&[1, 3], 3).unwrap();
+        assert!(AdapterChain::new(&[1, 2, 4], &rules).is_err());
+    }
+
+    #[test]
+    fn test_device_rating_overflow() {
+        let rules = ChainRules::puzzle().unwrap();
+        let err = AdapterChain::new(&[Joltage::MAX - 1], &rules)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "parse error: device rating {} + 3 is too high",
+                Joltage::MAX - 1
+            )
+        );
+        // A device rating of exactly the maximum is fine, so only the outlet gap is wrong here
+        let err = AdapterChain::new(&[Joltage::MAX - 3], &rules)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("at position 0 is not allowed"));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let err = |gaps: &[Joltage], device_offset| match ChainRules::new(gaps, device_offset) {
+            Err(crate::Error::Parse(err)) => err.to_string(),
+            other => panic!("expected parse error, got {:?}", other),
+        };
+        assert_eq!(err(&[], 3), "gaps must be positive, got []");
+        assert_eq!(err(&[3, 0, 1], 3), "gaps must be positive, got [0, 1, 3]");
+        assert_eq!(
+            err(&[1, 2], 3),
+            "device offset 3 must be one of the allowed gaps [1, 2]"
+        );
+    }
+
     #[test]
     fn test_count_chunk_permutations() {
         assert_eq!(count_chunk_permutations(&[1, 2, 3]), 2);
@@ -216,6 +439,54 @@ mod tests {
         assert_eq!(counter.next(), None);
     }
 
+    /// Chain of every adapter from 1 to `n`, with the device one higher.
+    fn contiguous_chain(n: Joltage, gaps: &[Joltage]) -> AdapterChain {
+        let adapters: Vec<Joltage> = (1..=n).collect();
+        AdapterChain::new(&adapters, &ChainRules::new(gaps, 1).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_count_arrangements_matches_brute_force() {
+        for n in 1..=12 {
+            let adapters: Vec<Joltage> = (1..=n).collect();
+            let chain = AdapterChain::new(&adapters, &ChainRules::puzzle().unwrap()).unwrap();
+            assert_eq!(
+                chain.count_arrangements(),
+                BigUint::from(count_arrangements_brute_force(&chain.joltages))
+            );
+        }
+        let data = read_input(&data_path!("day10_example2.txt")).unwrap();
+        let chain = AdapterChain::new(&data, &ChainRules::puzzle().unwrap()).unwrap();
+        assert_eq!(
+            chain.count_arrangements(),
+            BigUint::from(count_arrangements_brute_force(&chain.joltages))
+        );
+    }
+
+    #[test]
+    fn test_count_arrangements_gaps() {
+        let count = |gaps: &[Joltage]| contiguous_chain(10, gaps).count_arrangements();
+        assert_eq!(count(&[1]), BigUint::from(1_u32));
+        // Fibonacci
+        assert_eq!(count(&[1, 2]), BigUint::from(144_u32));
+        // Tribonacci
+        assert_eq!(count(&[1, 2, 3]), BigUint::from(504_u32));
+        assert_eq!(count(&[1, 3]), BigUint::from(41_u32));
+        // Any subset of the adapters
+        assert_eq!(
+            count(&(1..=11).collect::<Vec<_>>()),
+            BigUint::from(1024_u32)
+        );
+    }
+
+    #[test]
+    fn test_count_arrangements_overflow() {
+        let count = |n| contiguous_chain(n, &[1, 2, 3]).count_arrangements();
+        let big = count(200);
+        assert!(big > BigUint::from(u128::MAX));
+        assert_eq!(big, count(199) + count(198) + count(197));
+    }
+
     #[test]
     fn test_part2_example1() {
         assert_eq!(part2(data_path!("day10_example1.txt")).unwrap(), "8");
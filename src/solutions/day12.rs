@@ -1,3 +1,5 @@
+use std::cmp;
+use std::fmt::Write;
 use std::str::FromStr;
 
 use super::prelude::*;
@@ -46,10 +48,23 @@ enum Rotation {
 #[derive(Clone, Debug)]
 enum Action {
     Translate(Direction, i64),
+    /// Rotate by a number of quarter turns.
     Rotate(Rotation, i64),
     Forward(i64),
 }
 
+/// Convert a rotation in degrees to quarter turns, rejecting anything that isn't a whole number of
+/// quarter turns rather than rounding it.
+fn quarter_turns(degrees: i64) -> crate::Result<i64> {
+    if degrees % 90 == 0 {
+        Ok(degrees / 90)
+    } else {
+        Err(crate::Error::Parse(
+            format!("rotation of {} degrees is not a multiple of 90", degrees).into(),
+        ))
+    }
+}
+
 impl FromStr for Action {
     type Err = crate::Error;
 
@@ -61,18 +76,153 @@ impl FromStr for Action {
             "E" => Ok(Action::Translate(Direction::East, value)),
             "S" => Ok(Action::Translate(Direction::South, value)),
             "W" => Ok(Action::Translate(Direction::West, value)),
-            "L" => Ok(Action::Rotate(Rotation::Left, value)),
-            "R" => Ok(Action::Rotate(Rotation::Right, value)),
+            "L" => Ok(Action::Rotate(Rotation::Left, quarter_turns(value)?)),
+            "R" => Ok(Action::Rotate(Rotation::Right, quarter_turns(value)?)),
             "F" => Ok(Action::Forward(value)),
             a => Err(format!("unrecognised action: {}", a).into()),
         }
     }
 }
 
+/// Where the ship and its waypoint were at one point in its journey. The waypoint is relative to
+/// the ship.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct State {
+    position: Vector2D<i64>,
+    waypoint: Vector2D<i64>,
+}
+
+impl State {
+    fn absolute_waypoint(&self) -> Vector2D<i64> {
+        self.position + self.waypoint
+    }
+}
+
+/// Every state of a ship from when recording started, which is never empty.
+#[derive(Debug)]
+struct Trajectory(Vec<State>);
+
+impl Trajectory {
+    /// Smallest and largest coordinates of the ship and its waypoint over the whole trajectory.
+    fn bounds(&self) -> (Vector2D<i64>, Vector2D<i64>) {
+        let points = self
+            .0
+            .iter()
+            .flat_map(|state| vec![state.position, state.absolute_waypoint()]);
+        let mut min = self.0[0].position;
+        let mut max = min;
+        for point in points {
+            min = min.min(&point);
+            max = max.max(&point);
+        }
+        (min, max)
+    }
+
+    /// Draw the trajectory as an SVG image: the ship's path as a solid line, the waypoint's path as
+    /// a dashed line, and the start as a dot.
+    fn to_svg(&self) -> String {
+        let (min, max) = self.bounds();
+        let size = max - min;
+        let margin = cmp::max(size[0], size[1]) / 50 + 1;
+        let stroke = cmp::max(size[0], size[1]) / 500 + 1;
+        let polyline = |points: Vec<Vector2D<i64>>| -> String {
+            let points: Vec<String> = points
+                .iter()
+                .map(|p| format!("{},{}", p[0], p[1]))
+                .collect();
+            points.join(" ")
+        };
+        let mut output = String::new();
+        writeln!(
+            output,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            min[0] - margin,
+            min[1] - margin,
+            size[0] + 2 * margin,
+            size[1] + 2 * margin
+        )
+        .unwrap();
+        writeln!(
+            output,
+            concat!(
+                r#"  <polyline points="{}" fill="none" stroke="red" stroke-width="{}" "#,
+                r#"stroke-dasharray="{}"/>"#,
+            ),
+            polyline(self.0.iter().map(State::absolute_waypoint).collect()),
+            stroke,
+            stroke * 4
+        )
+        .unwrap();
+        writeln!(
+            output,
+            r#"  <polyline points="{}" fill="none" stroke="blue" stroke-width="{}"/>"#,
+            polyline(self.0.iter().map(|state| state.position).collect()),
+            stroke
+        )
+        .unwrap();
+        let start = self.0[0].position;
+        writeln!(
+            output,
+            r#"  <circle cx="{}" cy="{}" r="{}" fill="green"/>"#,
+            start[0],
+            start[1],
+            stroke * 3
+        )
+        .unwrap();
+        output.push_str("</svg>\n");
+        output
+    }
+
+    /// Draw the trajectory as text, scaled to fit in `width` by `height` characters: `#` for the
+    /// ship's path, `+` where the waypoint was, and `S` and `E` for where the ship started and
+    /// ended.
+    fn to_ascii(&self, width: usize, height: usize) -> crate::Result<String> {
+        if width == 0 || height == 0 {
+            return Err(format!("can't plot in {}x{} characters", width, height).into());
+        }
+        let (min, max) = self.bounds();
+        let size = max - min;
+        let cells = Vector([width as i64 - 1, height as i64 - 1]);
+        let to_cell = |point: Vector2D<i64>| -> Vector2D<i64> {
+            let offset = point - min;
+            Vector([
+                offset[0] * cells[0] / cmp::max(size[0], 1),
+                offset[1] * cells[1] / cmp::max(size[1], 1),
+            ])
+        };
+        let mut plot = vec![vec![b'.'; width]; height];
+        let mut set = |cell: Vector2D<i64>, c: u8| plot[cell[1] as usize][cell[0] as usize] = c;
+        for state in self.0.iter() {
+            set(to_cell(state.absolute_waypoint()), b'+');
+        }
+        for pair in self.0.windows(2) {
+            let (from, to) = (to_cell(pair[0].position), to_cell(pair[1].position));
+            let delta = to - from;
+            let steps = cmp::max(cmp::max(delta[0].abs(), delta[1].abs()), 1);
+            for i in 0..=steps {
+                set(
+                    from + Vector([delta[0] * i / steps, delta[1] * i / steps]),
+                    b'#',
+                );
+            }
+        }
+        set(to_cell(self.0[0].position), b'S');
+        set(to_cell(self.0[self.0.len() - 1].position), b'E');
+        let mut output = String::new();
+        for row in plot {
+            output.push_str(std::str::from_utf8(&row).unwrap());
+            output.push('\n');
+        }
+        Ok(output)
+    }
+}
+
 #[derive(Debug)]
 struct Ship {
     position: Vector2D<i64>,
     waypoint: Vector2D<i64>,
+    /// If recording, the state when recording started followed by the state after each action.
+    trajectory: Option<Trajectory>,
 }
 
 impl Ship {
@@ -80,6 +230,26 @@ impl Ship {
         Ship {
             position: Vector([0, 0]),
             waypoint,
+            trajectory: None,
+        }
+    }
+
+    fn state(&self) -> State {
+        State {
+            position: self.position,
+            waypoint: self.waypoint,
+        }
+    }
+
+    /// Start recording the ship's trajectory, from its current state.
+    fn record_trajectory(&mut self) {
+        self.trajectory = Some(Trajectory(vec![self.state()]));
+    }
+
+    fn record(&mut self) {
+        let state = self.state();
+        if let Some(trajectory) = self.trajectory.as_mut() {
+            trajectory.0.push(state);
         }
     }
 
@@ -95,12 +265,13 @@ impl Ship {
                     Rotation::Left => -1,
                     Rotation::Right => 1,
                 };
-                self.waypoint = rotate_vector(self.waypoint, (value / 90) * multiplier);
+                self.waypoint = rotate_vector(self.waypoint, value * multiplier);
             }
             Action::Forward(value) => {
                 self.position = self.position + self.waypoint * value;
             }
         }
+        self.record();
     }
 
     fn apply_via_waypoint(&mut self, action: Action) {
@@ -115,12 +286,13 @@ impl Ship {
                     Rotation::Left => -1,
                     Rotation::Right => 1,
                 };
-                self.waypoint = rotate_vector(self.waypoint, (value / 90) * multiplier);
+                self.waypoint = rotate_vector(self.waypoint, value * multiplier);
             }
             Action::Forward(value) => {
                 self.position = self.position + self.waypoint * value;
             }
         }
+        self.record();
     }
 }
 
@@ -130,22 +302,58 @@ fn read_input(input_path: &PathBuf) -> crate::Result<Vec<Action>> {
         .collect::<Result<Vec<_>, _>>()
 }
 
-fn part1(input_path: PathBuf) -> crate::Result<String> {
-    let actions = read_input(&input_path)?;
-    let mut state = Ship::new(Direction::East.into());
+/// Navigate by the actions in a file, either moving the ship directly (part 1) or moving its
+/// waypoint (part 2), and optionally recording the trajectory.
+fn navigate(input_path: &PathBuf, via_waypoint: bool, record: bool) -> crate::Result<Ship> {
+    let actions = read_input(input_path)?;
+    let mut ship = Ship::new(if via_waypoint {
+        Vector([10, -1])
+    } else {
+        Direction::East.into()
+    });
+    if record {
+        ship.record_trajectory();
+    }
     for action in actions {
-        state.apply_directly(action);
+        if via_waypoint {
+            ship.apply_via_waypoint(action);
+        } else {
+            ship.apply_directly(action);
+        }
     }
-    Ok(state.position.manhattan_length().to_string())
+    Ok(ship)
+}
+
+/// Navigate by the actions in a file, returning the recorded trajectory.
+fn navigate_recorded(input_path: &PathBuf, via_waypoint: bool) -> crate::Result<Trajectory> {
+    let ship = navigate(input_path, via_waypoint, true)?;
+    Ok(ship.trajectory.unwrap())
+}
+
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    let ship = navigate(&input_path, false, false)?;
+    Ok(ship.position.manhattan_length().to_string())
 }
 
 fn part2(input_path: PathBuf) -> crate::Result<String> {
-    let actions = read_input(&input_path)?;
-    let mut state = Ship::new(Vector([10, -1]));
-    for action in actions {
-        state.apply_via_waypoint(action.clone());
-    }
-    Ok(state.position.manhattan_length().to_string())
+    let ship = navigate(&input_path, true, false)?;
+    Ok(ship.position.manhattan_length().to_string())
+}
+
+/// Plot the route through a navigation file as an SVG image.
+pub fn plot_svg(input_path: PathBuf, via_waypoint: bool) -> crate::Result<String> {
+    Ok(navigate_recorded(&input_path, via_waypoint)?.to_svg())
+}
+
+/// Plot the route through a navigation file as text, `width` by `height` characters, which must
+/// both be at least 1.
+pub fn plot_ascii(
+    input_path: PathBuf,
+    via_waypoint: bool,
+    width: usize,
+    height: usize,
+) -> crate::Result<String> {
+    navigate_recorded(&input_path, via_waypoint)?.to_ascii(width, height)
 }
 
 pub fn register(runner: &mut crate::Runner) {
@@ -157,6 +365,72 @@ pub fn register(runner: &mut crate::Runner) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rotation_must_be_quarter_turns() {
+        assert!(matches!(
+            "R270".parse::<Action>(),
+            Ok(Action::Rotate(Rotation::Right, 3))
+        ));
+        assert!(matches!(
+            "L45".parse::<Action>(),
+            Err(crate::Error::Parse(_))
+        ));
+        assert!(matches!(
+            "R100".parse::<Action>(),
+            Err(crate::Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_trajectory() {
+        let ship = navigate(&data_path!("day12_example.txt"), true, false).unwrap();
+        assert!(ship.trajectory.is_none());
+        let trajectory = navigate_recorded(&data_path!("day12_example.txt"), true).unwrap();
+        let trajectory: Vec<(i64, i64, i64, i64)> = trajectory
+            .0
+            .iter()
+            .map(|s| (s.position[0], s.position[1], s.waypoint[0], s.waypoint[1]))
+            .collect();
+        assert_eq!(
+            trajectory,
+            vec![
+                (0, 0, 10, -1),
+                (100, -10, 10, -1),
+                (100, -10, 10, -4),
+                (170, -38, 10, -4),
+                (170, -38, 4, 10),
+                (214, 72, 4, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        let trajectory = navigate_recorded(&data_path!("day12_example.txt"), false).unwrap();
+        assert_eq!(
+            trajectory.to_ascii(10, 5).unwrap(),
+            concat!(
+                ".....####+\n",
+                "S#####..#.\n",
+                "........#.\n",
+                "........E.\n",
+                "........+.\n",
+            ),
+        );
+        assert_eq!(trajectory.to_ascii(1, 1).unwrap(), "E\n");
+        assert!(trajectory.to_ascii(0, 5).is_err());
+        assert!(plot_ascii(data_path!("day12_example.txt"), true, 10, 0).is_err());
+    }
+
+    #[test]
+    fn test_to_svg() {
+        let trajectory = navigate_recorded(&data_path!("day12_example.txt"), false).unwrap();
+        let svg = trajectory.to_svg();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r#"points="0,0 10,0 10,-3 17,-3 17,-3 17,8""#));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
     #[test]
     fn test_part1_example() {
         assert_eq!(part1(data_path!("day12_example.txt")).unwrap(), "25");
@@ -1,29 +1,69 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
 use num::Integer;
 
 use super::prelude::*;
 use crate::util;
 
+/// A slot in the bus schedule: the bus ID (if any) and its offset from the start of the schedule.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Bus {
+    offset: i64,
+    id: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+struct Schedule(Vec<Bus>);
+
+impl Schedule {
+    /// Iterate over `(offset, id)` for every slot that has a bus in it.
+    fn iter_buses(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.0
+            .iter()
+            .filter_map(|bus| bus.id.map(|id| (bus.offset, id)))
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(",")
+            .enumerate()
+            .map(|(i, b)| {
+                let id = if b == "x" {
+                    None
+                } else {
+                    let id = b.parse::<i64>()?;
+                    if id <= 0 {
+                        return Err(format!("invalid bus ID: {}", id).into());
+                    }
+                    Some(id)
+                };
+                Ok(Bus {
+                    offset: i as i64,
+                    id,
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()
+            .map(Schedule)
+    }
+}
+
 struct Data {
     departure: i64,
-    buses: Vec<Option<i64>>,
+    schedule: Schedule,
 }
 
 fn read_input(input_path: &PathBuf) -> crate::Result<Data> {
     let mut lines = util::read_lines(input_path);
-    let departure = lines.next().expect("departure time").parse()?;
-    let buses = lines
-        .next()
-        .expect("bus list")
-        .split(",")
-        .map(|b| {
-            if b == "x" {
-                None
-            } else {
-                Some(b.parse::<i64>().unwrap())
-            }
-        })
-        .collect::<Vec<_>>();
-    Ok(Data { departure, buses })
+    let departure = lines.next().ok_or("missing departure time")?.parse()?;
+    let schedule = lines.next().ok_or("missing bus list")?.parse()?;
+    Ok(Data {
+        departure,
+        schedule,
+    })
 }
 
 /// Part 1: "What is the ID of the earliest bus you can take to the airport multiplied by the number
@@ -33,36 +73,119 @@ fn read_input(input_path: &PathBuf) -> crate::Result<Data> {
 /// bus, sort by the delay, take the first one.
 fn part1(input_path: PathBuf) -> crate::Result<String> {
     let data = read_input(&input_path)?;
-    let mut departures: Vec<_> = data
-        .buses
-        .iter()
-        .filter_map(|bus| {
-            bus.map(|bus| {
-                let (_, rem) = data.departure.div_rem(&bus);
-                let delay = bus - rem;
-                (bus, delay)
-            })
+    let (bus, delay) = data
+        .schedule
+        .iter_buses()
+        .map(|(_offset, bus)| {
+            let (_, rem) = data.departure.div_rem(&bus);
+            let delay = bus - rem;
+            (bus, delay)
         })
-        .collect();
-    departures.sort_by_key(|(_bus, delay)| *delay);
-    let (bus, delay) = departures[0];
+        .min_by_key(|(_bus, delay)| *delay)
+        .ok_or("no buses in schedule")?;
     Ok((bus * delay).to_string())
 }
 
-#[allow(unused_variables)]
-fn part2(input_path: PathBuf) -> crate::Result<String> {
-    Err("unimplemented".into())
+/// Find the earliest timestamp `t` where every bus departs at `t + offset` by sieving.
+///
+/// Starting from `t = 0` with a step of 1, advance `t` by the current step until the next bus
+/// departs at its offset, then multiply the step up to the LCM of itself and that bus's ID so that
+/// every later candidate still satisfies all the buses seen so far.
+///
+/// The step can outgrow an `i64` before the answer does, so the search is done with `i128`.
+fn earliest_sequential_departure_sieve(schedule: &Schedule) -> crate::Result<i64> {
+    let mut t: i128 = 0;
+    let mut step: i128 = 1;
+    for (offset, id) in schedule.iter_buses() {
+        let (offset, id) = (offset as i128, id as i128);
+        // Candidates repeat modulo `id` after at most `id` steps, so give up after that
+        let mut attempts = 0;
+        while (t + offset).mod_floor(&id) != 0 {
+            if attempts == id {
+                return Err(
+                    format!("no departure satisfies bus {} at offset {}", id, offset).into(),
+                );
+            }
+            t = t.checked_add(step).ok_or_else(too_late)?;
+            attempts += 1;
+        }
+        step = (step / step.gcd(&id))
+            .checked_mul(id)
+            .ok_or_else(too_late)?;
+    }
+    i64::try_from(t).map_err(|_| too_late())
+}
+
+fn too_late() -> crate::Error {
+    "earliest departure is too late to represent".into()
+}
+
+/// Find the earliest timestamp `t` where every bus departs at `t + offset` using the Chinese
+/// remainder theorem.
+///
+/// Each bus gives a congruence `t ≡ -offset (mod id)`. Congruences are combined pairwise, which
+/// also works when bus IDs are not coprime as long as the congruences agree.
+fn earliest_sequential_departure_crt(schedule: &Schedule) -> crate::Result<i64> {
+    let (mut a, mut m): (i128, i128) = (0, 1);
+    for (offset, id) in schedule.iter_buses() {
+        let (b, n) = ((-offset as i128).mod_floor(&(id as i128)), id as i128);
+        let egcd = m.extended_gcd(&n);
+        if (b - a) % egcd.gcd != 0 {
+            return Err(format!("no departure satisfies bus {} at offset {}", id, offset).into());
+        }
+        let lcm = (m / egcd.gcd).checked_mul(n).ok_or_else(too_late)?;
+        // Both factors are reduced modulo `n / gcd` first, so their product fits
+        let n_reduced = n / egcd.gcd;
+        let k = (((b - a) / egcd.gcd).mod_floor(&n_reduced) * egcd.x.mod_floor(&n_reduced))
+            .mod_floor(&n_reduced);
+        a = m
+            .checked_mul(k)
+            .and_then(|mk| mk.checked_add(a))
+            .ok_or_else(too_late)?;
+        m = lcm;
+    }
+    i64::try_from(a).map_err(|_| too_late())
+}
+
+/// Part 2: "What is the earliest timestamp such that all of the listed bus IDs depart at offsets
+/// matching their positions in the list?"
+fn part2<F: Fn(&Schedule) -> crate::Result<i64>>(
+    input_path: PathBuf,
+    implementation: F,
+) -> crate::Result<String> {
+    let data = read_input(&input_path)?;
+    implementation(&data.schedule).map(|t| t.to_string())
 }
 
 pub fn register(runner: &mut crate::Runner) {
     runner.add("day13part1", || part1(data_path!("day13_input.txt")));
-    runner.add("day13part2", || part2(data_path!("day13_input.txt")));
+    runner.add("day13part2_sieve", || {
+        part2(
+            data_path!("day13_input.txt"),
+            earliest_sequential_departure_sieve,
+        )
+    });
+    runner.add("day13part2_crt", || {
+        part2(
+            data_path!("day13_input.txt"),
+            earliest_sequential_departure_crt,
+        )
+    });
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLES: &[(&str, i64)] = &[
+        ("7,13,x,x,59,x,31,19", 1068781),
+        ("17,x,13,19", 3417),
+        ("67,7,59,61", 754018),
+        ("67,x,7,59,61", 779210),
+        ("67,7,x,59,61", 1261476),
+        ("1789,37,47,1889", 1202161486),
+    ];
+
     #[test]
     fn test_part1_example1() {
         assert_eq!(part1(data_path!("day13_example1.txt")).unwrap(), "295");
@@ -73,8 +196,110 @@ mod tests {
         assert_eq!(part1(data_path!("day13_input.txt")).unwrap(), "1895");
     }
 
+    #[test]
+    fn test_schedule_offsets() {
+        let schedule: Schedule = "17,x,13,19".parse().unwrap();
+        assert_eq!(
+            schedule.0,
+            vec![
+                Bus {
+                    offset: 0,
+                    id: Some(17)
+                },
+                Bus {
+                    offset: 1,
+                    id: None
+                },
+                Bus {
+                    offset: 2,
+                    id: Some(13)
+                },
+                Bus {
+                    offset: 3,
+                    id: Some(19)
+                },
+            ]
+        );
+        assert!("17,y,13".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn test_earliest_sequential_departure_sieve() {
+        for &(s, expected) in EXAMPLES {
+            let schedule: Schedule = s.parse().unwrap();
+            assert_eq!(
+                earliest_sequential_departure_sieve(&schedule).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_earliest_sequential_departure_crt() {
+        for &(s, expected) in EXAMPLES {
+            let schedule: Schedule = s.parse().unwrap();
+            assert_eq!(
+                earliest_sequential_departure_crt(&schedule).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_earliest_sequential_departure_overflow() {
+        // The answer is about 5.2e21, which doesn't fit in an i64
+        let schedule: Schedule = "1000003,x,1000033,1000037,1000039".parse().unwrap();
+        assert!(earliest_sequential_departure_sieve(&schedule).is_err());
+        assert!(earliest_sequential_departure_crt(&schedule).is_err());
+        // Large bus IDs are fine while the answer still fits
+        let schedule: Schedule = "1000003,1000033".parse().unwrap();
+        assert_eq!(
+            earliest_sequential_departure_sieve(&schedule).unwrap(),
+            earliest_sequential_departure_crt(&schedule).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_part1_no_buses() {
+        assert!(part1(data_path!("day13_no_buses.txt")).is_err());
+    }
+
+    #[test]
+    fn test_earliest_sequential_departure_inconsistent() {
+        let schedule: Schedule = "4,6".parse().unwrap();
+        assert!(earliest_sequential_departure_sieve(&schedule).is_err());
+        assert!(earliest_sequential_departure_crt(&schedule).is_err());
+    }
+
+    #[test]
+    fn test_part2_example1() {
+        assert_eq!(
+            part2(
+                data_path!("day13_example1.txt"),
+                earliest_sequential_departure_crt
+            )
+            .unwrap(),
+            "1068781"
+        );
+    }
+
     #[test]
     fn test_part2_solution() {
-        assert_eq!(part2(data_path!("day13_input.txt")).unwrap(), "");
+        assert_eq!(
+            part2(
+                data_path!("day13_input.txt"),
+                earliest_sequential_departure_sieve
+            )
+            .unwrap(),
+            "840493039281088"
+        );
+        assert_eq!(
+            part2(
+                data_path!("day13_input.txt"),
+                earliest_sequential_departure_crt
+            )
+            .unwrap(),
+            "840493039281088"
+        );
     }
 }
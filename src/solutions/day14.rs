@@ -1,117 +1,287 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::num::ParseIntError;
 use std::str::FromStr;
 
+use num::{BigUint, One, PrimInt, Unsigned};
+
 use super::prelude::*;
 use crate::util;
 
-type Word = u64;
+/// Width of the puzzle's system, in bits.
+const WIDTH: u32 = 36;
+
+/// Unsigned integer type used for addresses and values.
+///
+/// Counts and sums of memory can need more bits than the word itself, so they are big integers.
+trait Word: PrimInt + Unsigned + Hash + fmt::Display + FromStr<Err = ParseIntError> {}
+
+impl<T: PrimInt + Unsigned + Hash + fmt::Display + FromStr<Err = ParseIntError>> Word for T {}
+
+/// Get a word with the lowest `width` bits set.
+fn low_bits<W: Word>(width: u32) -> W {
+    if width >= W::zero().count_zeros() {
+        !W::zero()
+    } else {
+        (W::one() << width as usize) - W::one()
+    }
+}
 
-#[derive(Clone)]
-struct Mask {
-    pattern: Word,
-    value: Word,
+fn to_big<W: Word>(value: W) -> BigUint {
+    // Every primitive unsigned integer fits in a u128
+    BigUint::from(value.to_u128().unwrap())
 }
 
-impl Mask {
-    fn identity() -> Self {
+fn check_fits<W: Word>(what: &str, value: W, width: u32) -> crate::Result<()> {
+    if value & !low_bits::<W>(width) == W::zero() {
+        Ok(())
+    } else {
+        Err(crate::Error::Parse(
+            format!("{} {} doesn't fit in {} bits", what, value, width).into(),
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Mask<W: Word> {
+    width: u32,
+    pattern: W,
+    value: W,
+}
+
+impl<W: Word> Mask<W> {
+    fn identity(width: u32) -> Self {
         Mask {
-            pattern: !0,
-            value: 0,
+            width,
+            pattern: low_bits(width),
+            value: W::zero(),
         }
     }
 
-    fn apply(&self, value: Word) -> Word {
+    fn apply(&self, value: W) -> W {
         value & self.pattern | self.value
     }
 
-    fn iter_addrs(&self, addr: Word) -> impl Iterator<Item = Word> {
+    fn iter_addrs(&self, addr: W) -> impl Iterator<Item = W> {
         // Clear floating bits, apply fixed bits
         let addr = (addr & !self.pattern) | self.value;
-        let floating_bits: Vec<usize> = (0..36)
-            .filter_map(|i| {
-                if self.pattern & (1 << i) != 0 {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .collect();
-        (0_u64..(2_u64.pow(floating_bits.len() as u32))).map(move |floating| {
-            let mut next = addr;
-            for (i, bit) in floating_bits.iter().cloned().enumerate() {
-                if floating & (1 << i) != 0 {
-                    next |= 1 << bit;
-                }
-            }
-            next
+        let pattern = self.pattern;
+        // Count through every combination of floating bits in increasing order: setting all the
+        // other bits makes adding 1 carry straight to the next floating bit, and it overflows
+        // after the last combination
+        let mut next = Some(W::zero());
+        std::iter::from_fn(move || {
+            let floating = next?;
+            next = (floating | !pattern)
+                .checked_add(&W::one())
+                .map(|n| n & pattern);
+            Some(addr | floating)
         })
     }
 }
 
-impl FromStr for Mask {
+impl<W: Word> FromStr for Mask<W> {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut pattern: Word = 0;
-        let mut value: Word = 0;
+        let width = s.len() as u32;
+        if width > W::zero().count_zeros() {
+            return Err(crate::Error::Parse(
+                format!("mask is too wide for word type: {}", s).into(),
+            ));
+        }
+        let mut pattern = W::zero();
+        let mut value = W::zero();
         for (i, b) in s.bytes().rev().enumerate() {
             match b {
                 b'X' => {
-                    pattern |= 1 << i;
+                    pattern = pattern | (W::one() << i);
                 }
                 b'0' => {
                     // Nothing to do here, bit is already 0 in both pattern and value
                 }
                 b'1' => {
-                    value |= 1 << i;
+                    value = value | (W::one() << i);
+                }
+                b => {
+                    return Err(crate::Error::Parse(
+                        format!("unexpected byte in mask: {}", b).into(),
+                    ))
                 }
-                b => return Err(format!("unexpected byte in mask: {}", b).into()),
             }
         }
-        Ok(Mask { pattern, value })
+        Ok(Mask {
+            width,
+            pattern,
+            value,
+        })
+    }
+}
+
+impl<W: Word> fmt::Display for Mask<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in (0..self.width as usize).rev() {
+            let bit = W::one() << i;
+            let c = if self.pattern & bit != W::zero() {
+                'X'
+            } else if self.value & bit != W::zero() {
+                '1'
+            } else {
+                '0'
+            };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of addresses, as a ternary pattern where each bit is either fixed or floating.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct AddressPattern<W: Word> {
+    floating: W,
+    /// Values of the fixed bits; floating bits are always 0 here.
+    fixed: W,
+}
+
+impl<W: Word> AddressPattern<W> {
+    /// The addresses written by a version 2 write to `addr` under `mask`.
+    fn new(mask: &Mask<W>, addr: W) -> Self {
+        AddressPattern {
+            floating: mask.pattern,
+            fixed: (addr | mask.value) & !mask.pattern,
+        }
+    }
+
+    /// Number of concrete addresses matching the pattern.
+    fn len(&self) -> BigUint {
+        BigUint::one() << self.floating.count_ones() as usize
+    }
+
+    fn intersects(&self, other: &AddressPattern<W>) -> bool {
+        (self.fixed ^ other.fixed) & !self.floating & !other.floating == W::zero()
+    }
+
+    /// Split the addresses in `self` but not in `other` into disjoint patterns.
+    ///
+    /// Each bit that floats in `self` but is fixed in `other` is pinned in turn: pinning it to the
+    /// opposite of `other`'s value gives a pattern disjoint from `other`, and pinning it to the
+    /// same value leaves the rest to be split further.
+    fn subtract(&self, other: &AddressPattern<W>) -> Vec<AddressPattern<W>> {
+        if !self.intersects(other) {
+            return vec![*self];
+        }
+        let mut remaining = *self;
+        let mut parts = Vec::new();
+        let mut to_pin = self.floating & !other.floating;
+        while to_pin != W::zero() {
+            let bit = W::one() << to_pin.trailing_zeros() as usize;
+            to_pin = to_pin & !bit;
+            remaining.floating = remaining.floating & !bit;
+            parts.push(AddressPattern {
+                floating: remaining.floating,
+                fixed: remaining.fixed | (!other.fixed & bit),
+            });
+            remaining.fixed = remaining.fixed | (other.fixed & bit);
+        }
+        // Whatever remains is entirely inside `other`
+        parts
+    }
+}
+
+/// Version 2 memory that stores each write as the pattern of addresses it still owns, instead of
+/// expanding floating bits into concrete addresses.
+///
+/// Patterns are kept disjoint by subtracting each new write from all earlier ones, so summing the
+/// memory is just adding up `value * len` for each pattern.
+struct SymbolicMemory<W: Word> {
+    writes: Vec<(AddressPattern<W>, W)>,
+}
+
+impl<W: Word> SymbolicMemory<W> {
+    fn new() -> Self {
+        SymbolicMemory { writes: Vec::new() }
+    }
+
+    fn write(&mut self, addrs: AddressPattern<W>, value: W) {
+        let mut writes = Vec::with_capacity(self.writes.len() + 1);
+        for (old, old_value) in self.writes.drain(..) {
+            writes.extend(old.subtract(&addrs).into_iter().map(|p| (p, old_value)));
+        }
+        writes.push((addrs, value));
+        self.writes = writes;
+    }
+
+    fn sum(&self) -> BigUint {
+        self.writes
+            .iter()
+            .map(|(addrs, value)| addrs.len() * to_big(*value))
+            .sum()
     }
 }
 
-enum Op {
-    Mask(Mask),
-    Set(Word, Word),
+enum Op<W: Word> {
+    Mask(Mask<W>),
+    Set(W, W),
 }
 
-impl FromStr for Op {
+impl<W: Word> FromStr for Op<W> {
     type Err = crate::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let (lhs, rhs) = util::str_partition(s, " = ");
         if lhs == "mask" {
             Ok(Op::Mask(rhs.parse()?))
-        } else if lhs.starts_with("mem[") {
-            Ok(Op::Set(lhs[4..(lhs.len() - 1)].parse()?, rhs.parse()?))
+        } else if let Some(addr) = lhs.strip_prefix("mem[").and_then(|a| a.strip_suffix(']')) {
+            Ok(Op::Set(addr.parse()?, rhs.parse()?))
         } else {
-            Err(format!("unknown operation: {}", s).into())
+            Err(crate::Error::Parse(
+                format!("unknown operation: {}", s).into(),
+            ))
         }
     }
 }
 
-fn read_input(input_path: &PathBuf) -> crate::Result<Vec<Op>> {
+impl<W: Word> Op<W> {
+    /// Check the operation only uses the lowest `width` bits.
+    fn validate(&self, width: u32) -> crate::Result<()> {
+        match self {
+            Op::Mask(mask) if mask.width != width => Err(crate::Error::Parse(
+                format!("mask {} is not {} bits wide", mask, width).into(),
+            )),
+            Op::Mask(_) => Ok(()),
+            Op::Set(addr, value) => {
+                check_fits("address", *addr, width)?;
+                check_fits("value", *value, width)
+            }
+        }
+    }
+}
+
+fn read_input<W: Word>(input_path: &PathBuf, width: u32) -> crate::Result<Vec<Op<W>>> {
     util::read_lines(input_path)
-        .map(|line| line.parse())
+        .map(|line| {
+            let op: Op<W> = line.parse()?;
+            op.validate(width)?;
+            Ok(op)
+        })
         .collect()
 }
 
-struct System {
-    mem: HashMap<Word, Word>,
-    mask: Mask,
+struct System<W: Word> {
+    mem: HashMap<W, W>,
+    mask: Mask<W>,
 }
 
-impl System {
-    fn new() -> Self {
+impl<W: Word> System<W> {
+    fn new(width: u32) -> Self {
         System {
             mem: HashMap::new(),
-            mask: Mask::identity(),
+            mask: Mask::identity(width),
         }
     }
 
-    fn apply(&mut self, op: &Op) {
+    fn apply(&mut self, op: &Op<W>) {
         match op {
             Op::Mask(mask) => {
                 self.mask = mask.clone();
@@ -123,13 +293,13 @@ impl System {
         }
     }
 
-    fn run(&mut self, ops: &[Op]) {
+    fn run(&mut self, ops: &[Op<W>]) {
         for op in ops {
             self.apply(op);
         }
     }
 
-    fn apply_v2(&mut self, op: &Op) {
+    fn apply_v2(&mut self, op: &Op<W>) {
         match op {
             Op::Mask(mask) => {
                 self.mask = mask.clone();
@@ -142,32 +312,70 @@ impl System {
         }
     }
 
-    fn run_v2(&mut self, ops: &[Op]) {
+    fn run_v2(&mut self, ops: &[Op<W>]) {
         for op in ops {
             self.apply_v2(op);
         }
     }
+
+    fn sum(&self) -> BigUint {
+        self.mem.values().map(|&value| to_big(value)).sum()
+    }
+}
+
+fn sum_memory_v1<W: Word>(program: &[Op<W>], width: u32) -> BigUint {
+    let mut system = System::new(width);
+    system.run(program);
+    system.sum()
+}
+
+/// Sum memory after running version 2 by writing every concrete address.
+///
+/// Takes time exponential in the number of floating bits, so only really useful to check
+/// `sum_memory_v2_symbolic()`.
+fn sum_memory_v2_expanding<W: Word>(program: &[Op<W>], width: u32) -> BigUint {
+    let mut system = System::new(width);
+    system.run_v2(program);
+    system.sum()
+}
+
+fn sum_memory_v2_symbolic<W: Word>(program: &[Op<W>], width: u32) -> BigUint {
+    let mut mask = Mask::identity(width);
+    let mut memory = SymbolicMemory::new();
+    for op in program {
+        match op {
+            Op::Mask(new_mask) => {
+                mask = new_mask.clone();
+            }
+            Op::Set(addr, value) => {
+                memory.write(AddressPattern::new(&mask, *addr), *value);
+            }
+        }
+    }
+    memory.sum()
 }
 
 fn part1(input_path: PathBuf) -> crate::Result<String> {
-    let program = read_input(&input_path)?;
-    let mut system = System::new();
-    system.run(&program);
-    let sum: Word = system.mem.values().sum();
-    Ok(sum.to_string())
+    let program = read_input::<u64>(&input_path, WIDTH)?;
+    Ok(sum_memory_v1(&program, WIDTH).to_string())
 }
 
-fn part2(input_path: PathBuf) -> crate::Result<String> {
-    let program = read_input(&input_path)?;
-    let mut system = System::new();
-    system.run_v2(&program);
-    let sum: Word = system.mem.values().sum();
-    Ok(sum.to_string())
+fn part2<F: Fn(&[Op<u64>], u32) -> BigUint>(
+    input_path: PathBuf,
+    sum_memory: F,
+) -> crate::Result<String> {
+    let program = read_input(&input_path, WIDTH)?;
+    Ok(sum_memory(&program, WIDTH).to_string())
 }
 
 pub fn register(runner: &mut crate::Runner) {
     runner.add("day14part1", || part1(data_path!("day14_input.txt")));
-    runner.add("day14part2", || part2(data_path!("day14_input.txt")));
+    runner.add("day14part2_expanding", || {
+        part2(data_path!("day14_input.txt"), sum_memory_v2_expanding)
+    });
+    runner.add("day14part2_symbolic", || {
+        part2(data_path!("day14_input.txt"), sum_memory_v2_symbolic)
+    });
 }
 
 #[cfg(test)]
@@ -189,20 +397,176 @@ mod tests {
 
     #[test]
     fn test_mask_addrs() {
-        let mask: Mask = "000000000000000000000000000000X1001X".parse().unwrap();
-        let addrs: Vec<Word> = mask.iter_addrs(42).collect();
+        let mask: Mask<u64> = "000000000000000000000000000000X1001X".parse().unwrap();
+        let addrs: Vec<u64> = mask.iter_addrs(42).collect();
         assert_eq!(addrs, vec![26, 27, 58, 59]);
     }
 
+    #[test]
+    fn test_mask_display() {
+        let s = "000000000000000000000000000000X1001X";
+        assert_eq!(s.parse::<Mask<u64>>().unwrap().to_string(), s);
+        assert_eq!(Mask::<u8>::identity(4).to_string(), "XXXX");
+    }
+
+    #[test]
+    fn test_validation() {
+        let err =
+            |line: &str, width| match line.parse::<Op<u16>>().and_then(|op| op.validate(width)) {
+                Err(crate::Error::Parse(err)) => err.to_string(),
+                other => panic!("expected parse error, got {:?}", other.map(|_| ())),
+            };
+        assert_eq!(err("mask = X10", 4), "mask X10 is not 4 bits wide");
+        assert_eq!(err("mem[16] = 1", 4), "address 16 doesn't fit in 4 bits");
+        assert_eq!(err("mem[1] = 255", 4), "value 255 doesn't fit in 4 bits");
+        assert_eq!(err("mask = X1Z0", 4), "unexpected byte in mask: 90");
+        assert_eq!(
+            err("mask = 00000000000000000", 17),
+            "mask is too wide for word type: 00000000000000000"
+        );
+        assert_eq!(err("mem[ = 5", 4), "unknown operation: mem[ = 5");
+        assert_eq!(err("mem[5 = 1", 4), "unknown operation: mem[5 = 1");
+        assert_eq!(
+            err("mem[] = 1", 4),
+            "cannot parse integer from empty string"
+        );
+        assert_eq!(err("set[1] = 1", 4), "unknown operation: set[1] = 1");
+        assert!("mem[15] = 15"
+            .parse::<Op<u16>>()
+            .unwrap()
+            .validate(4)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_other_width() {
+        let program: Vec<Op<u8>> = ["mask = X1X0", "mem[2] = 3", "mask = 00X1", "mem[8] = 4"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        for op in program.iter() {
+            op.validate(4).unwrap();
+        }
+        // v1: 3 masked by X1X0 is 6 at address 2, then 4 masked by 00X1 is 1 at address 8
+        assert_eq!(sum_memory_v1(&program, 4), BigUint::from(7_u32));
+        // v2: 3 written to {4, 6, 12, 14}, then 4 written to {9, 11}
+        assert_eq!(sum_memory_v2_expanding(&program, 4), BigUint::from(20_u32));
+        assert_eq!(sum_memory_v2_symbolic(&program, 4), BigUint::from(20_u32));
+    }
+
+    #[test]
+    fn test_full_width_sums_dont_overflow() {
+        // Every address of a u8 holds 255, which sums to far more than a u8 can hold
+        let program: Vec<Op<u8>> = ["mask = XXXXXXXX", "mem[0] = 255"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        let all_addrs: Vec<u8> = match &program[0] {
+            Op::Mask(mask) => mask.iter_addrs(0).collect(),
+            _ => unreachable!(),
+        };
+        assert_eq!(all_addrs, (0..=255).collect::<Vec<u8>>());
+        assert_eq!(
+            sum_memory_v2_expanding(&program, 8),
+            BigUint::from(256_u32 * 255)
+        );
+        assert_eq!(
+            sum_memory_v2_symbolic(&program, 8),
+            BigUint::from(256_u32 * 255)
+        );
+
+        let program: Vec<Op<u64>> = [&format!("mask = {}", "X".repeat(64)), "mem[0] = 3"]
+            .iter()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        assert_eq!(
+            sum_memory_v2_symbolic(&program, 64),
+            BigUint::from(3_u32) << 64
+        );
+    }
+
+    #[test]
+    fn test_address_pattern_subtract() {
+        let mask: Mask<u64> = "000000000000000000000000000000X1001X".parse().unwrap();
+        let pattern = AddressPattern::new(&mask, 42);
+        assert_eq!(pattern.len(), BigUint::from(4_u32));
+        let other =
+            AddressPattern::new(&"0000000000000000000000000000000000X0".parse().unwrap(), 24);
+        // `other` is {24, 26}, so only 26 is removed
+        let parts = pattern.subtract(&other);
+        let mut addrs: Vec<u64> = parts
+            .iter()
+            .flat_map(|p| {
+                let mask = Mask {
+                    width: WIDTH,
+                    pattern: p.floating,
+                    value: p.fixed,
+                };
+                mask.iter_addrs(0)
+            })
+            .collect();
+        addrs.sort_unstable();
+        assert_eq!(addrs, vec![27, 58, 59]);
+        assert!(pattern.subtract(&pattern).is_empty());
+    }
+
+    #[test]
+    fn test_symbolic_matches_expanding() {
+        let program: Vec<Op<u64>> = [
+            "mask = 0000000000000000000000000000X1X0XX1X",
+            "mem[42] = 100",
+            "mask = 00000000000000000000000000000000X0XX",
+            "mem[26] = 1",
+            "mask = 000000000000000000000000000XXX000001",
+            "mem[3] = 7",
+            "mem[42] = 9",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect();
+        assert_eq!(
+            sum_memory_v2_symbolic(&program, WIDTH),
+            sum_memory_v2_expanding(&program, WIDTH)
+        );
+    }
+
+    #[test]
+    fn test_symbolic_many_floating_bits() {
+        let program: Vec<Op<u64>> = [
+            "mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX",
+            "mem[0] = 2",
+            "mask = 1XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX",
+            "mem[0] = 1",
+        ]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect();
+        assert_eq!(
+            sum_memory_v2_symbolic(&program, WIDTH),
+            BigUint::from(3_u64 << 35)
+        );
+    }
+
     #[test]
     fn test_part2_example2() {
-        assert_eq!(part2(data_path!("day14_example2.txt")).unwrap(), "208");
+        assert_eq!(
+            part2(data_path!("day14_example2.txt"), sum_memory_v2_expanding).unwrap(),
+            "208"
+        );
+        assert_eq!(
+            part2(data_path!("day14_example2.txt"), sum_memory_v2_symbolic).unwrap(),
+            "208"
+        );
     }
 
     #[test]
     fn test_part2_solution() {
         assert_eq!(
-            part2(data_path!("day14_input.txt")).unwrap(),
+            part2(data_path!("day14_input.txt"), sum_memory_v2_expanding).unwrap(),
+            "4795970362286"
+        );
+        assert_eq!(
+            part2(data_path!("day14_input.txt"), sum_memory_v2_symbolic).unwrap(),
             "4795970362286"
         );
     }
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use super::prelude::*;
 use crate::util;
 
-fn read_input(input_path: &PathBuf) -> crate::Result<Vec<u64>> {
+fn read_input(input_path: &PathBuf) -> crate::Result<Vec<u32>> {
     let line = util::read_lines(input_path)
         .next()
         .ok_or(crate::Error::Other("no input".into()))?;
@@ -12,44 +12,134 @@ fn read_input(input_path: &PathBuf) -> crate::Result<Vec<u64>> {
         .collect::<Result<Vec<_>, _>>()
 }
 
-struct Game {
-    turns: u64,
-    previous: u64,
-    last_seen: HashMap<u64, u64>,
+/// Storage for the turn on which each number was last spoken.
+///
+/// Turns count from 1, and every number spoken is smaller than the number of turns so far (or is
+/// part of the seed), so turns and numbers both fit in `u32` for any reasonable game.
+pub trait LastSeen {
+    /// Create empty storage, expecting a game of `turns` turns.
+    fn with_capacity(turns: usize) -> Self;
+    fn get(&self, number: u32) -> Option<u32>;
+    /// Record that `number` was spoken on `turn`, returning the turn it was previously spoken on.
+    fn insert(&mut self, number: u32, turn: u32) -> Option<u32>;
+}
+
+pub struct HashMapLastSeen(HashMap<u32, u32>);
+
+impl LastSeen for HashMapLastSeen {
+    fn with_capacity(_turns: usize) -> Self {
+        HashMapLastSeen(HashMap::new())
+    }
+
+    fn get(&self, number: u32) -> Option<u32> {
+        self.0.get(&number).cloned()
+    }
+
+    fn insert(&mut self, number: u32, turn: u32) -> Option<u32> {
+        self.0.insert(number, turn)
+    }
+}
+
+/// Flat array indexed by number, where 0 means never spoken.
+///
+/// Preallocating one slot per turn means the array never has to grow for numbers produced by the
+/// game itself, and is much faster than hashing for the dense range of numbers the game produces.
+/// Only seed numbers can be larger than that, so those go in a map instead of growing the array.
+pub struct VecLastSeen {
+    dense: Vec<u32>,
+    sparse: HashMap<u32, u32>,
+}
+
+impl LastSeen for VecLastSeen {
+    fn with_capacity(turns: usize) -> Self {
+        VecLastSeen {
+            dense: vec![0; turns],
+            sparse: HashMap::new(),
+        }
+    }
+
+    fn get(&self, number: u32) -> Option<u32> {
+        match self.dense.get(number as usize) {
+            Some(&turn) if turn != 0 => Some(turn),
+            Some(_) => None,
+            None => self.sparse.get(&number).cloned(),
+        }
+    }
+
+    fn insert(&mut self, number: u32, turn: u32) -> Option<u32> {
+        match self.dense.get_mut(number as usize) {
+            Some(slot) => match std::mem::replace(slot, turn) {
+                0 => None,
+                previous => Some(previous),
+            },
+            None => self.sparse.insert(number, turn),
+        }
+    }
+}
+
+pub struct Game<L: LastSeen> {
+    turns: u32,
+    previous: u32,
+    /// Turn each number was last spoken on, except for `previous`, which is only recorded once the
+    /// next number has been worked out.
+    last_seen: L,
 }
 
-impl Game {
-    fn new(seed: &[u64]) -> Self {
-        assert!(seed.len() > 0);
-        Game {
-            turns: seed.len() as u64,
-            previous: seed.last().cloned().unwrap(),
-            last_seen: seed[..seed.len() - 1]
-                .iter()
-                .enumerate()
-                .map(|(i, &x)| (x, (i + 1) as u64))
-                .collect(),
+impl<L: LastSeen> Game<L> {
+    /// Start a game from `seed`, expecting to play `capacity` turns in total.
+    pub fn new(seed: &[u32], capacity: usize) -> crate::Result<Self> {
+        let (&previous, rest) = seed.split_last().ok_or("empty seed")?;
+        let mut last_seen = L::with_capacity(capacity);
+        for (i, &x) in rest.iter().enumerate() {
+            last_seen.insert(x, (i + 1) as u32);
+        }
+        Ok(Game {
+            turns: seed.len() as u32,
+            previous,
+            last_seen,
+        })
+    }
+
+    /// The turn on which `number` was last spoken, if it has been spoken yet.
+    pub fn last_spoken(&self, number: u32) -> Option<u32> {
+        if number == self.previous {
+            Some(self.turns)
+        } else {
+            self.last_seen.get(number)
         }
     }
 }
 
-impl Iterator for Game {
-    type Item = u64;
+impl<L: LastSeen> Iterator for Game<L> {
+    type Item = u32;
 
     fn next(&mut self) -> Option<Self::Item> {
         let last_seen = self
             .last_seen
-            .get(&self.previous)
-            .cloned()
+            .insert(self.previous, self.turns)
             .unwrap_or(self.turns);
         let next = self.turns - last_seen;
-        self.last_seen.insert(self.previous, self.turns);
         self.turns += 1;
         self.previous = next;
         Some(next)
     }
 }
 
+/// Find the `n`th number spoken in the game starting from `seed`.
+pub fn nth_number<L: LastSeen>(seed: &[u32], n: usize) -> crate::Result<u32> {
+    if seed.is_empty() {
+        return Err("empty seed".into());
+    }
+    if n <= seed.len() {
+        return seed
+            .get(n.wrapping_sub(1))
+            .cloned()
+            .ok_or("no result".into());
+    }
+    let mut game = Game::<L>::new(seed, n)?;
+    game.nth(n - seed.len() - 1).ok_or("no result".into())
+}
+
 /// Part 1: given the `Game` above, find the 2020th number.
 ///
 /// Simply run the game until the 2020th step.
@@ -58,25 +148,30 @@ fn part1(input_path: PathBuf) -> crate::Result<String> {
     part1_impl(&seed).map(|x| x.to_string())
 }
 
-fn part1_impl(seed: &[u64]) -> crate::Result<u64> {
-    let mut game = Game::new(seed);
-    game.nth(2020 - seed.len() - 1).ok_or("no result".into())
+fn part1_impl(seed: &[u32]) -> crate::Result<u32> {
+    nth_number::<VecLastSeen>(seed, 2020)
 }
 
-fn part2(input_path: PathBuf) -> crate::Result<String> {
+/// Part 2: as part 1, but find the 30,000,000th number.
+///
+/// The game is the same, but the choice of `LastSeen` storage matters a lot at this size.
+fn part2<L: LastSeen>(input_path: PathBuf) -> crate::Result<String> {
     let seed = read_input(&input_path)?;
-    part2_impl(&seed).map(|x| x.to_string())
+    part2_impl::<L>(&seed).map(|x| x.to_string())
 }
 
-fn part2_impl(seed: &[u64]) -> crate::Result<u64> {
-    let mut game = Game::new(seed);
-    game.nth(30_000_000 - seed.len() - 1)
-        .ok_or("no result".into())
+fn part2_impl<L: LastSeen>(seed: &[u32]) -> crate::Result<u32> {
+    nth_number::<L>(seed, 30_000_000)
 }
 
 pub fn register(runner: &mut crate::Runner) {
     runner.add("day15part1", || part1(data_path!("day15_input.txt")));
-    runner.add("day15part2", || part2(data_path!("day15_input.txt")));
+    runner.add("day15part2_hashmap", || {
+        part2::<HashMapLastSeen>(data_path!("day15_input.txt"))
+    });
+    runner.add("day15part2_vec", || {
+        part2::<VecLastSeen>(data_path!("day15_input.txt"))
+    });
 }
 
 #[cfg(test)]
@@ -99,19 +194,82 @@ mod tests {
         assert_eq!(part1(data_path!("day15_input.txt")).unwrap(), "249");
     }
 
+    #[test]
+    fn test_backends_agree() {
+        let seed = [0, 3, 6];
+        let by_hashmap: Vec<u32> = Game::<HashMapLastSeen>::new(&seed, 0)
+            .unwrap()
+            .take(2000)
+            .collect();
+        let by_vec: Vec<u32> = Game::<VecLastSeen>::new(&seed, 0)
+            .unwrap()
+            .take(2000)
+            .collect();
+        assert_eq!(&by_vec[..7], &[0, 3, 3, 1, 0, 4, 0]);
+        assert_eq!(by_hashmap, by_vec);
+        assert_eq!(nth_number::<HashMapLastSeen>(&seed, 2020).unwrap(), 436);
+        assert_eq!(nth_number::<VecLastSeen>(&seed, 2).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_empty_seed() {
+        assert!(Game::<VecLastSeen>::new(&[], 10).is_err());
+        assert!(nth_number::<VecLastSeen>(&[], 10).is_err());
+        assert!(nth_number::<HashMapLastSeen>(&[], 0).is_err());
+    }
+
+    #[test]
+    fn test_large_seed_numbers() {
+        // Numbers beyond the preallocated turns must not grow the array to match
+        let seed = [4_000_000_000, 1, 4_000_000_000];
+        let by_hashmap: Vec<u32> = Game::<HashMapLastSeen>::new(&seed, 10)
+            .unwrap()
+            .take(50)
+            .collect();
+        let by_vec: Vec<u32> = Game::<VecLastSeen>::new(&seed, 10)
+            .unwrap()
+            .take(50)
+            .collect();
+        assert_eq!(&by_vec[..3], &[2, 0, 0]);
+        assert_eq!(by_hashmap, by_vec);
+        assert_eq!(
+            nth_number::<VecLastSeen>(&[4_000_000_000, 1], 4).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_last_spoken() {
+        // 0, 3, 6, 0, 3, 3, 1, 0, 4, 0
+        let mut game = Game::<VecLastSeen>::new(&[0, 3, 6], 10).unwrap();
+        game.nth(6);
+        assert_eq!(game.last_spoken(0), Some(10));
+        assert_eq!(game.last_spoken(3), Some(6));
+        assert_eq!(game.last_spoken(6), Some(3));
+        assert_eq!(game.last_spoken(4), Some(9));
+        assert_eq!(game.last_spoken(2), None);
+    }
+
     #[test]
     fn test_part2_impl() {
-        assert_eq!(part2_impl(&vec![0, 3, 6]).unwrap(), 175594);
-        assert_eq!(part2_impl(&vec![1, 3, 2]).unwrap(), 2578);
-        assert_eq!(part2_impl(&vec![2, 1, 3]).unwrap(), 3544142);
-        assert_eq!(part2_impl(&vec![1, 2, 3]).unwrap(), 261214);
-        assert_eq!(part2_impl(&vec![2, 3, 1]).unwrap(), 6895259);
-        assert_eq!(part2_impl(&vec![3, 2, 1]).unwrap(), 18);
-        assert_eq!(part2_impl(&vec![3, 1, 2]).unwrap(), 362);
+        assert_eq!(part2_impl::<VecLastSeen>(&[0, 3, 6]).unwrap(), 175594);
+        assert_eq!(part2_impl::<VecLastSeen>(&[1, 3, 2]).unwrap(), 2578);
+        assert_eq!(part2_impl::<VecLastSeen>(&[2, 1, 3]).unwrap(), 3544142);
+        assert_eq!(part2_impl::<VecLastSeen>(&[1, 2, 3]).unwrap(), 261214);
+        assert_eq!(part2_impl::<VecLastSeen>(&[2, 3, 1]).unwrap(), 6895259);
+        assert_eq!(part2_impl::<VecLastSeen>(&[3, 2, 1]).unwrap(), 18);
+        assert_eq!(part2_impl::<VecLastSeen>(&[3, 1, 2]).unwrap(), 362);
     }
 
     #[test]
     fn test_part2_solution() {
-        assert_eq!(part2(data_path!("day15_input.txt")).unwrap(), "41687");
+        assert_eq!(
+            part2::<HashMapLastSeen>(data_path!("day15_input.txt")).unwrap(),
+            "41687"
+        );
+        assert_eq!(
+            part2::<VecLastSeen>(data_path!("day15_input.txt")).unwrap(),
+            "41687"
+        );
     }
 }
@@ -1,5 +1,5 @@
 use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::{Deref, RangeInclusive};
 use std::str::FromStr;
 
@@ -66,7 +66,10 @@ impl Ruleset {
     }
 
     fn is_valid(&self, ticket: &Ticket) -> bool {
-        self.error_rate(ticket) == 0
+        // Can't just check for an error rate of 0, because an invalid value might be 0
+        ticket
+            .iter()
+            .all(|&v| self.matching_fields_for_value(v).next().is_some())
     }
 }
 
@@ -157,32 +160,17 @@ fn part2(input_path: PathBuf) -> crate::Result<String> {
         }
     }
 
-    // Find the correct field mapping: a field corresponds to a position if all values in that
+    // Find the correct field mapping: a field can correspond to a position if all values in that
     // position match the rules for that field
-    let mut field_mapping: HashMap<&str, usize> = HashMap::new();
-    while field_mapping.len() < input.ruleset.0.len() {
-        let mut changed = 0_usize;
-        for (i, counts) in match_counts.iter().enumerate() {
-            let candidates: Vec<_> = counts
-                .iter()
-                .filter_map(|(&field, &count)| {
-                    if count == valid_tickets.len() && !field_mapping.contains_key(field) {
-                        Some(field)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-            if candidates.len() == 1 {
-                field_mapping.insert(candidates[0], i);
-                changed += 1;
+    let mut candidates: HashMap<&str, HashSet<usize>> = HashMap::new();
+    for (i, counts) in match_counts.iter().enumerate() {
+        for (&field, &count) in counts.iter() {
+            if count == valid_tickets.len() {
+                candidates.entry(field).or_default().insert(i);
             }
         }
-        // Give up if we can't map any more
-        if changed == 0 {
-            break;
-        }
     }
+    let field_mapping = util::resolve_unique_mapping(candidates)?;
 
     // Get just the fields we're interested in (and check we found them all)
     let departure_fields: Vec<_> = field_mapping
@@ -220,6 +208,16 @@ mod tests {
         assert_eq!(part1(data_path!("day16_input.txt")).unwrap(), "27870");
     }
 
+    #[test]
+    fn test_is_valid_with_invalid_zero() {
+        let ruleset = Ruleset(vec!["class: 1-3 or 5-7".parse().unwrap()]);
+        let ticket = Ticket(vec![0, 5]);
+        // The error rate can't tell this apart from a valid ticket
+        assert_eq!(ruleset.error_rate(&ticket), 0);
+        assert!(!ruleset.is_valid(&ticket));
+        assert!(ruleset.is_valid(&Ticket(vec![1, 5])));
+    }
+
     #[test]
     fn test_part2_solution() {
         assert_eq!(
@@ -1,78 +1,295 @@
+use std::iter::Peekable;
+
 use super::prelude::*;
 use crate::util;
 
-#[derive(Debug, Eq, PartialEq)]
+/// Byte range within the source expression, used for error reporting.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+#[derive(Debug)]
+struct SyntaxError {
+    span: Span,
+    message: String,
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+fn syntax_error<S: Into<String>>(span: Span, message: S) -> crate::Error {
+    crate::Error::Parse(Box::new(SyntaxError {
+        span,
+        message: message.into(),
+    }))
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 enum Token {
-    Value(u64),
+    Value(i64),
     Add,
+    Sub,
     Mul,
     LParen,
     RParen,
 }
 
-fn tokenise_expr(s: &str) -> impl Iterator<Item = Token> + '_ {
-    s.bytes().filter_map(move |b| match b {
-        b' ' => None,
-        b'0'..=b'9' => Some(Token::Value((b - b'0') as u64)),
-        b'+' => Some(Token::Add),
-        b'*' => Some(Token::Mul),
-        b'(' => Some(Token::LParen),
-        b')' => Some(Token::RParen),
-        _ => panic!("unrecognised byte: {}", b),
-    })
-}
-
-fn evaluate_tokens<I: Iterator<Item = Token>>(tokens: &mut I) -> u64 {
-    let mut acc: u64 = 0;
-    let mut next_op = Token::Add;
-    while let Some(t) = tokens.next() {
-        match t {
-            Token::Value(v) => match next_op {
-                Token::Add => {
-                    acc += v;
+/// Split `s` into tokens, each paired with its location in `s`.
+///
+/// Also checks that parentheses are balanced, so that the error can point at the offending
+/// parenthesis rather than wherever the parser happens to notice.
+fn tokenise_expr(s: &str) -> crate::Result<Vec<(Token, Span)>> {
+    let mut tokens = Vec::new();
+    let mut open_parens: Vec<Span> = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let span = Span::new(start, start + c.len_utf8());
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '0'..='9' => {
+                let mut end = span.end;
+                while let Some(&(i, '0'..='9')) = chars.peek() {
+                    end = i + 1;
+                    chars.next();
                 }
-                Token::Mul => {
-                    acc *= v;
+                let span = Span::new(start, end);
+                let value = s[start..end]
+                    .parse()
+                    .map_err(|_| syntax_error(span, "integer literal too large"))?;
+                tokens.push((Token::Value(value), span));
+                continue;
+            }
+            '+' => Token::Add,
+            '-' => Token::Sub,
+            '*' => Token::Mul,
+            '(' => {
+                open_parens.push(span);
+                Token::LParen
+            }
+            ')' => {
+                if open_parens.pop().is_none() {
+                    return Err(syntax_error(span, "unmatched ')'"));
                 }
-                _ => panic!("unexpected next_op: {:?}", next_op),
-            },
-            Token::Add | Token::Mul => {
-                next_op = t;
+                Token::RParen
             }
-            Token::LParen => {
-                let v = evaluate_tokens(tokens);
-                match next_op {
-                    Token::Add => {
-                        acc += v;
-                    }
-                    Token::Mul => {
-                        acc *= v;
+            c => {
+                return Err(syntax_error(
+                    span,
+                    format!("unrecognised character {:?}", c),
+                ))
+            }
+        };
+        tokens.push((token, span));
+    }
+    match open_parens.pop() {
+        Some(span) => Err(syntax_error(span, "unmatched '('")),
+        None => Ok(tokens),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl BinOp {
+    fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::Add => Some(BinOp::Add),
+            Token::Sub => Some(BinOp::Sub),
+            Token::Mul => Some(BinOp::Mul),
+            _ => None,
+        }
+    }
+
+    /// Apply the operator, or `None` if the result overflows.
+    fn apply(&self, lhs: i64, rhs: i64) -> Option<i64> {
+        match self {
+            BinOp::Add => lhs.checked_add(rhs),
+            BinOp::Sub => lhs.checked_sub(rhs),
+            BinOp::Mul => lhs.checked_mul(rhs),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+        }
+    }
+}
+
+/// Operator precedence table: higher numbers bind more tightly, all operators are left-associative.
+type Precedence = [(BinOp, u8)];
+
+/// "... the operators have the same precedence, and are evaluated left-to-right ..."
+const EQUAL_PRECEDENCE: &Precedence = &[(BinOp::Add, 1), (BinOp::Sub, 1), (BinOp::Mul, 1)];
+
+/// "... addition is evaluated before multiplication."
+const ADD_BEFORE_MUL: &Precedence = &[(BinOp::Add, 2), (BinOp::Sub, 2), (BinOp::Mul, 1)];
+
+/// Unary minus binds more tightly than any binary operator.
+const PREFIX_BINDING_POWER: u8 = u8::MAX;
+
+#[derive(Debug, Eq, PartialEq)]
+enum Expr {
+    Value(i64),
+    Neg(Box<Expr>),
+    BinOp(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self) -> crate::Result<i64> {
+        let result = match self {
+            Expr::Value(v) => Some(*v),
+            Expr::Neg(inner) => inner.evaluate()?.checked_neg(),
+            Expr::BinOp(op, lhs, rhs) => op.apply(lhs.evaluate()?, rhs.evaluate()?),
+        };
+        result.ok_or_else(|| format!("overflow evaluating {}", self).into())
+    }
+}
+
+/// Pretty-print with every binary operation parenthesised, making the parsed precedence explicit.
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Value(v) => write!(f, "{}", v),
+            Expr::Neg(inner) => write!(f, "-{}", inner),
+            Expr::BinOp(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op.symbol(), rhs),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Peekable<std::vec::IntoIter<(Token, Span)>>,
+    precedence: &'a Precedence,
+    /// Empty span at the end of the source, for reporting errors about missing tokens.
+    eof: Span,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &str, precedence: &'a Precedence) -> crate::Result<Self> {
+        Ok(Parser {
+            tokens: tokenise_expr(s)?.into_iter().peekable(),
+            precedence,
+            eof: Span::new(s.len(), s.len()),
+        })
+    }
+
+    fn binding_power(&self, op: BinOp, span: Span) -> crate::Result<u8> {
+        self.precedence
+            .iter()
+            .find(|(o, _)| *o == op)
+            .map(|&(_, bp)| bp)
+            .ok_or_else(|| syntax_error(span, format!("no precedence for operator {:?}", op)))
+    }
+
+    /// Parse a value, a parenthesised expression, or a negated operand.
+    fn parse_operand(&mut self) -> crate::Result<Expr> {
+        match self.tokens.next() {
+            Some((Token::Value(v), _)) => Ok(Expr::Value(v)),
+            Some((Token::Sub, _)) => {
+                let inner = self.parse_expr(PREFIX_BINDING_POWER)?;
+                Ok(Expr::Neg(Box::new(inner)))
+            }
+            Some((Token::LParen, _)) => {
+                let inner = self.parse_expr(0)?;
+                match self.tokens.next() {
+                    Some((Token::RParen, _)) => Ok(inner),
+                    Some((t, span)) => {
+                        Err(syntax_error(span, format!("expected ')', got {:?}", t)))
                     }
-                    _ => panic!("unexpected next_op: {:?}", next_op),
+                    None => Err(syntax_error(self.eof, "expected ')'")),
                 }
             }
-            Token::RParen => {
-                return acc;
+            Some((t, span)) => Err(syntax_error(
+                span,
+                format!("expected value or '(', got {:?}", t),
+            )),
+            None => Err(syntax_error(self.eof, "unexpected end of expression")),
+        }
+    }
+
+    /// Parse an expression with a Pratt parser, only consuming operators that bind more tightly
+    /// than `min_bp`.
+    ///
+    /// Recursing with the binding power of the operator just consumed means operators of equal
+    /// precedence end the right-hand side, which makes them left-associative.
+    fn parse_expr(&mut self, min_bp: u8) -> crate::Result<Expr> {
+        let mut lhs = self.parse_operand()?;
+
+        loop {
+            let (op, span) = match self.tokens.peek() {
+                None | Some((Token::RParen, _)) => break,
+                Some((t, span)) => match BinOp::from_token(t) {
+                    Some(op) => (op, *span),
+                    None => {
+                        return Err(syntax_error(
+                            *span,
+                            format!("expected operator, got {:?}", t),
+                        ))
+                    }
+                },
+            };
+            let bp = self.binding_power(op, span)?;
+            if bp <= min_bp {
+                break;
             }
+            self.tokens.next();
+            let rhs = self.parse_expr(bp)?;
+            lhs = Expr::BinOp(op, Box::new(lhs), Box::new(rhs));
         }
+
+        Ok(lhs)
+    }
+}
+
+fn parse(s: &str, precedence: &Precedence) -> crate::Result<Expr> {
+    let mut parser = Parser::new(s, precedence)?;
+    let expr = parser.parse_expr(0)?;
+    match parser.tokens.next() {
+        None => Ok(expr),
+        Some((t, span)) => Err(syntax_error(span, format!("unexpected {:?}", t))),
     }
-    acc
 }
 
-fn evaluate_expr(s: &str) -> u64 {
-    let mut tokens = tokenise_expr(s);
-    evaluate_tokens(&mut tokens)
+fn evaluate_expr(s: &str, precedence: &Precedence) -> crate::Result<i64> {
+    parse(s, precedence)?.evaluate()
+}
+
+fn sum_expressions(input_path: &PathBuf, precedence: &Precedence) -> crate::Result<i64> {
+    util::read_lines(input_path).try_fold(0_i64, |sum, line| {
+        sum.checked_add(evaluate_expr(&line, precedence)?)
+            .ok_or_else(|| "overflow summing expressions".into())
+    })
 }
 
 fn part1(input_path: PathBuf) -> crate::Result<String> {
-    Ok(util::read_lines(&input_path)
-        .map(|line| evaluate_expr(&line))
-        .sum::<u64>()
-        .to_string())
+    sum_expressions(&input_path, EQUAL_PRECEDENCE).map(|sum| sum.to_string())
 }
 
 fn part2(input_path: PathBuf) -> crate::Result<String> {
-    Err("unimplemented".into())
+    sum_expressions(&input_path, ADD_BEFORE_MUL).map(|sum| sum.to_string())
 }
 
 pub fn register(runner: &mut crate::Runner) {
@@ -84,10 +301,25 @@ pub fn register(runner: &mut crate::Runner) {
 mod tests {
     use super::*;
 
+    fn tokens(s: &str) -> Vec<Token> {
+        tokenise_expr(s)
+            .unwrap()
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect()
+    }
+
+    fn error_span<T: std::fmt::Debug>(result: crate::Result<T>) -> Span {
+        match result.unwrap_err() {
+            crate::Error::Parse(err) => err.downcast_ref::<SyntaxError>().unwrap().span,
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
+
     #[test]
     fn test_tokenise_expr() {
         assert_eq!(
-            tokenise_expr("1 + 2 * 3 + 4 * 5 + 6").collect::<Vec<Token>>(),
+            tokens("1 + 2 * 3 + 4 * 5 + 6"),
             vec![
                 Token::Value(1),
                 Token::Add,
@@ -103,7 +335,7 @@ mod tests {
             ]
         );
         assert_eq!(
-            tokenise_expr("1 + (2 * 3) + (4 * (5 + 6))").collect::<Vec<Token>>(),
+            tokens("1 + (2 * 3) + (4 * (5 + 6))"),
             vec![
                 Token::Value(1),
                 Token::Add,
@@ -126,22 +358,134 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tokenise_expr_multi_digit() {
+        assert_eq!(
+            tokenise_expr("12 -(345)").unwrap(),
+            vec![
+                (Token::Value(12), Span::new(0, 2)),
+                (Token::Sub, Span::new(3, 4)),
+                (Token::LParen, Span::new(4, 5)),
+                (Token::Value(345), Span::new(5, 8)),
+                (Token::RParen, Span::new(8, 9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenise_expr_errors() {
+        assert_eq!(error_span(tokenise_expr("1 + 2 % 3")), Span::new(6, 7));
+        assert_eq!(error_span(tokenise_expr("(1 + 2)) * 3")), Span::new(7, 8));
+        assert_eq!(error_span(tokenise_expr("((1 + 2) * 3")), Span::new(0, 1));
+        assert_eq!(
+            error_span(tokenise_expr("1 + 99999999999999999999")),
+            Span::new(4, 24)
+        );
+    }
+
     #[test]
     fn test_evaluate_expr() {
-        assert_eq!(evaluate_expr("1 + 2 * 3 + 4 * 5 + 6"), 71);
-        assert_eq!(evaluate_expr("1 + (2 * 3) + (4 * (5 + 6))"), 51);
-        assert_eq!(evaluate_expr("2 * 3 + (4 * 5)"), 26);
-        assert_eq!(evaluate_expr("5 + (8 * 3 + 9 + 3 * 4 * 3)"), 437);
+        let cases = [
+            ("1 + 2 * 3 + 4 * 5 + 6", 71),
+            ("1 + (2 * 3) + (4 * (5 + 6))", 51),
+            ("2 * 3 + (4 * 5)", 26),
+            ("5 + (8 * 3 + 9 + 3 * 4 * 3)", 437),
+            ("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))", 12240),
+            ("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2", 13632),
+        ];
+        for &(expr, expected) in cases.iter() {
+            assert_eq!(evaluate_expr(expr, EQUAL_PRECEDENCE).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_expr_add_before_mul() {
+        let cases = [
+            ("1 + 2 * 3 + 4 * 5 + 6", 231),
+            ("1 + (2 * 3) + (4 * (5 + 6))", 51),
+            ("2 * 3 + (4 * 5)", 46),
+            ("5 + (8 * 3 + 9 + 3 * 4 * 3)", 1445),
+            ("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))", 669060),
+            ("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2", 23340),
+        ];
+        for &(expr, expected) in cases.iter() {
+            assert_eq!(evaluate_expr(expr, ADD_BEFORE_MUL).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_evaluate_expr_overflow() {
+        let err = |expr| {
+            evaluate_expr(expr, EQUAL_PRECEDENCE)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            err("9223372036854775807 + 1"),
+            "overflow evaluating (9223372036854775807 + 1)"
+        );
+        assert_eq!(
+            err("2 * (0 - 9223372036854775807 - 1) + 3"),
+            "overflow evaluating (2 * ((0 - 9223372036854775807) - 1))"
+        );
+        assert_eq!(
+            err("-(0 - 9223372036854775807 - 1)"),
+            "overflow evaluating -((0 - 9223372036854775807) - 1)"
+        );
         assert_eq!(
-            evaluate_expr("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))"),
-            12240
+            evaluate_expr("0 - 9223372036854775807 - 1", EQUAL_PRECEDENCE).unwrap(),
+            i64::MIN
+        );
+    }
+
+    #[test]
+    fn test_parse_display() {
+        assert_eq!(
+            parse("2 * 3 + 4", EQUAL_PRECEDENCE).unwrap().to_string(),
+            "((2 * 3) + 4)"
+        );
+        assert_eq!(
+            parse("2 * 3 + 4", ADD_BEFORE_MUL).unwrap().to_string(),
+            "(2 * (3 + 4))"
         );
         assert_eq!(
-            evaluate_expr("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2"),
-            13632
+            parse("1 + (2 * 3)", ADD_BEFORE_MUL).unwrap().to_string(),
+            "(1 + (2 * 3))"
         );
     }
 
+    #[test]
+    fn test_evaluate_expr_subtraction() {
+        assert_eq!(evaluate_expr("10 - 4 - 3", EQUAL_PRECEDENCE).unwrap(), 3);
+        assert_eq!(evaluate_expr("2 * 10 - 4", EQUAL_PRECEDENCE).unwrap(), 16);
+        assert_eq!(evaluate_expr("2 * 10 - 4", ADD_BEFORE_MUL).unwrap(), 12);
+        assert_eq!(evaluate_expr("-3 * -(2 + 5)", ADD_BEFORE_MUL).unwrap(), 21);
+        assert_eq!(evaluate_expr("5 - -2", EQUAL_PRECEDENCE).unwrap(), 7);
+        assert_eq!(
+            parse("-2 + 3", ADD_BEFORE_MUL).unwrap().to_string(),
+            "(-2 + 3)"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(error_span(parse("1 +", EQUAL_PRECEDENCE)), Span::new(3, 3));
+        assert_eq!(
+            error_span(parse("(1 + 2", EQUAL_PRECEDENCE)),
+            Span::new(0, 1)
+        );
+        assert_eq!(
+            error_span(parse("1 + 2)", EQUAL_PRECEDENCE)),
+            Span::new(5, 6)
+        );
+        assert_eq!(error_span(parse("1 2", EQUAL_PRECEDENCE)), Span::new(2, 3));
+        assert_eq!(
+            error_span(parse("1 + * 2", EQUAL_PRECEDENCE)),
+            Span::new(4, 5)
+        );
+        assert_eq!(error_span(parse("()", EQUAL_PRECEDENCE)), Span::new(1, 2));
+    }
+
     #[test]
     fn test_part1_solution() {
         assert_eq!(
@@ -152,6 +496,9 @@ mod tests {
 
     #[test]
     fn test_part2_solution() {
-        assert_eq!(part2(data_path!("day18_input.txt")).unwrap(), "");
+        assert_eq!(
+            part2(data_path!("day18_input.txt")).unwrap(),
+            "360029542265462"
+        );
     }
 }
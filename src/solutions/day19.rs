@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use super::prelude::*;
+use crate::util;
+
+type RuleId = usize;
+
+/// The rule that whole messages must match.
+const START: RuleId = 0;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Rule {
+    /// Match a literal string, e.g. `"a"`.
+    Literal(String),
+    /// Match any one of several sequences of other rules, e.g. `1 2 | 3 4`.
+    Alternatives(Vec<Vec<RuleId>>),
+}
+
+impl FromStr for Rule {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('"') {
+            if s.len() < 3 || !s.ends_with('"') {
+                return Err(format!("invalid literal rule: {}", s).into());
+            }
+            Ok(Rule::Literal(s[1..(s.len() - 1)].to_owned()))
+        } else {
+            s.split(" | ")
+                .map(|alt| {
+                    let seq = alt
+                        .split_whitespace()
+                        .map(|id| id.parse::<RuleId>())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if seq.is_empty() {
+                        Err(format!("empty alternative in rule: {}", s).into())
+                    } else {
+                        Ok(seq)
+                    }
+                })
+                .collect::<crate::Result<Vec<_>>>()
+                .map(Rule::Alternatives)
+        }
+    }
+}
+
+/// A partially matched alternative: `alts[alt]` of rule `rule`, matched up to `dot`, having started
+/// at input position `origin`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Item {
+    rule: RuleId,
+    alt: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// A context-free grammar of numbered rules.
+struct Grammar {
+    rules: HashMap<RuleId, Rule>,
+}
+
+impl Grammar {
+    fn new() -> Self {
+        Grammar {
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Add or replace a rule from its `<id>: <rule>` definition.
+    fn insert_str(&mut self, s: &str) -> crate::Result<()> {
+        let (raw_id, raw_rule) = util::str_partition(s, ": ");
+        self.rules.insert(raw_id.parse()?, raw_rule.parse()?);
+        Ok(())
+    }
+
+    /// Check the start rule exists and every rule only refers to rules that exist.
+    fn validate(&self) -> crate::Result<()> {
+        if !self.rules.contains_key(&START) {
+            return Err(format!("missing start rule {}", START).into());
+        }
+        for (id, rule) in self.rules.iter() {
+            if let Rule::Alternatives(alts) = rule {
+                for &other in alts.iter().flatten() {
+                    if !self.rules.contains_key(&other) {
+                        return Err(format!("rule {} refers to missing rule {}", id, other).into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn alternatives(&self, id: RuleId) -> &[Vec<RuleId>] {
+        match &self.rules[&id] {
+            Rule::Alternatives(alts) => alts,
+            Rule::Literal(_) => &[],
+        }
+    }
+
+    /// Does `message` match rule `start` exactly?
+    ///
+    /// Uses an Earley recogniser, so any context-free rules are supported, including recursive
+    /// rules like `8: 42 | 42 8` and even left-recursive rules like `8: 8 42 | 42`. Literal rules
+    /// are scanned directly against the input, and so may be longer than one character.
+    ///
+    /// Other rules are assumed to exist, as checked by `validate()`.
+    fn matches(&self, start: RuleId, message: &str) -> crate::Result<bool> {
+        let start_rule = self
+            .rules
+            .get(&start)
+            .ok_or_else(|| format!("no such rule: {}", start))?;
+        let input = message.as_bytes();
+        let mut sets: Vec<Vec<Item>> = vec![Vec::new(); input.len() + 1];
+        let mut seen: Vec<HashSet<Item>> = vec![HashSet::new(); input.len() + 1];
+
+        fn add(sets: &mut [Vec<Item>], seen: &mut [HashSet<Item>], pos: usize, item: Item) {
+            if seen[pos].insert(item) {
+                sets[pos].push(item);
+            }
+        }
+
+        for alt in 0..self.alternatives(start).len() {
+            let item = Item {
+                rule: start,
+                alt,
+                dot: 0,
+                origin: 0,
+            };
+            add(&mut sets, &mut seen, 0, item);
+        }
+
+        for pos in 0..=input.len() {
+            // Items are appended to the current set while it is being processed
+            let mut i = 0;
+            while i < sets[pos].len() {
+                let item = sets[pos][i];
+                i += 1;
+                let seq = &self.alternatives(item.rule)[item.alt];
+                if item.dot == seq.len() {
+                    // Complete: advance everything that was waiting on this rule at `origin`
+                    let mut j = 0;
+                    while j < sets[item.origin].len() {
+                        let waiting = sets[item.origin][j];
+                        j += 1;
+                        let waiting_seq = &self.alternatives(waiting.rule)[waiting.alt];
+                        if waiting_seq.get(waiting.dot) == Some(&item.rule) {
+                            let advanced = Item {
+                                dot: waiting.dot + 1,
+                                ..waiting
+                            };
+                            add(&mut sets, &mut seen, pos, advanced);
+                        }
+                    }
+                    continue;
+                }
+                let next = seq[item.dot];
+                match &self.rules[&next] {
+                    Rule::Literal(literal) => {
+                        // Scan: advance into a later set if the literal matches here
+                        if input[pos..].starts_with(literal.as_bytes()) {
+                            let advanced = Item {
+                                dot: item.dot + 1,
+                                ..item
+                            };
+                            add(&mut sets, &mut seen, pos + literal.len(), advanced);
+                        }
+                    }
+                    Rule::Alternatives(alts) => {
+                        // Predict: start matching every alternative of the next rule here
+                        for alt in 0..alts.len() {
+                            let predicted = Item {
+                                rule: next,
+                                alt,
+                                dot: 0,
+                                origin: pos,
+                            };
+                            add(&mut sets, &mut seen, pos, predicted);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(match start_rule {
+            Rule::Literal(literal) => literal == message,
+            Rule::Alternatives(alts) => sets[input.len()].iter().any(|item| {
+                item.rule == start && item.origin == 0 && item.dot == alts[item.alt].len()
+            }),
+        })
+    }
+}
+
+struct Input {
+    grammar: Grammar,
+    messages: Vec<String>,
+}
+
+fn read_input(input_path: &PathBuf) -> crate::Result<Input> {
+    let file = File::open(input_path)?;
+    let mut lines = io::BufReader::new(file).lines();
+    let mut grammar = Grammar::new();
+    for line in &mut lines {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+        grammar.insert_str(&line)?;
+    }
+    grammar.validate()?;
+    let messages = lines.collect::<Result<Vec<_>, _>>()?;
+    Ok(Input { grammar, messages })
+}
+
+fn count_matches(input: &Input) -> crate::Result<usize> {
+    let mut count = 0;
+    for message in input.messages.iter() {
+        if input.grammar.matches(START, message)? {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Part 1: "How many messages completely match rule 0?"
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    let input = read_input(&input_path)?;
+    Ok(count_matches(&input)?.to_string())
+}
+
+/// Part 2: as part 1, but with rules 8 and 11 replaced by rules that loop.
+///
+/// The replacement rules are just more context-free rules, so the same matching works unchanged.
+fn part2(input_path: PathBuf) -> crate::Result<String> {
+    let mut input = read_input(&input_path)?;
+    input.grammar.insert_str("8: 42 | 42 8")?;
+    input.grammar.insert_str("11: 42 31 | 42 11 31")?;
+    input.grammar.validate()?;
+    Ok(count_matches(&input)?.to_string())
+}
+
+pub fn register(runner: &mut crate::Runner) {
+    runner.add("day19part1", || part1(data_path!("day19_input.txt")));
+    runner.add("day19part2", || part2(data_path!("day19_input.txt")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar(rules: &[&str]) -> Grammar {
+        let mut grammar = Grammar::new();
+        for rule in rules {
+            grammar.insert_str(rule).unwrap();
+        }
+        grammar.validate().unwrap();
+        grammar
+    }
+
+    #[test]
+    fn test_rule_from_str() {
+        assert_eq!("\"a\"".parse::<Rule>().unwrap(), Rule::Literal("a".into()));
+        assert_eq!(
+            "1 2 | 3".parse::<Rule>().unwrap(),
+            Rule::Alternatives(vec![vec![1, 2], vec![3]])
+        );
+        assert!("\"\"".parse::<Rule>().is_err());
+        assert!("1 | ".parse::<Rule>().is_err());
+        assert!("1 x".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn test_grammar_validate() {
+        let mut grammar = Grammar::new();
+        grammar.insert_str("0: 1 2").unwrap();
+        grammar.insert_str("1: \"a\"").unwrap();
+        assert!(grammar.validate().is_err());
+
+        let mut grammar = Grammar::new();
+        grammar.insert_str("1: \"a\"").unwrap();
+        assert_eq!(
+            grammar.validate().unwrap_err().to_string(),
+            "missing start rule 0"
+        );
+        assert!(grammar.matches(START, "a").is_err());
+        assert!(grammar.matches(1, "a").unwrap());
+    }
+
+    #[test]
+    fn test_grammar_matches_recursive() {
+        let right = grammar(&["0: 1 | 1 0", "1: \"ab\""]);
+        let left = grammar(&["0: 1 | 0 1", "1: \"ab\""]);
+        for g in [right, left].iter() {
+            assert!(g.matches(0, "ab").unwrap());
+            assert!(g.matches(0, "ababab").unwrap());
+            assert!(!g.matches(0, "").unwrap());
+            assert!(!g.matches(0, "aba").unwrap());
+        }
+        let nested = grammar(&["0: 1 2 | 1 0 2", "1: \"a\"", "2: \"b\""]);
+        assert!(nested.matches(0, "aaabbb").unwrap());
+        assert!(!nested.matches(0, "aaabb").unwrap());
+    }
+
+    #[test]
+    fn test_part1_example1() {
+        assert_eq!(part1(data_path!("day19_example1.txt")).unwrap(), "2");
+    }
+
+    #[test]
+    fn test_part1_example2() {
+        assert_eq!(part1(data_path!("day19_example2.txt")).unwrap(), "3");
+    }
+
+    #[test]
+    fn test_part2_example2() {
+        assert_eq!(part2(data_path!("day19_example2.txt")).unwrap(), "12");
+    }
+}
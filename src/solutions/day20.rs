@@ -0,0 +1,421 @@
+use std::collections::{HashMap, HashSet};
+
+use super::prelude::*;
+use crate::util::Grid2D;
+use crate::vector::{Vector, Vector2D};
+
+type Edge = u64;
+
+/// One of the 8 ways a square grid can be rotated and flipped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Orientation {
+    flipped: bool,
+    /// Number of clockwise quarter turns, applied after flipping.
+    rotations: u8,
+}
+
+impl Orientation {
+    fn all() -> impl Iterator<Item = Orientation> {
+        [false, true]
+            .iter()
+            .flat_map(|&flipped| (0..4).map(move |rotations| Orientation { flipped, rotations }))
+    }
+
+    fn index(&self) -> usize {
+        (self.flipped as usize) * 4 + self.rotations as usize
+    }
+
+    /// Find the point in the original `size`x`size` grid that appears at `point` when the grid is
+    /// viewed in this orientation.
+    fn source_point(&self, point: Vector2D<i64>, size: i64) -> Vector2D<i64> {
+        let Vector([mut x, mut y]) = point;
+        // Undo the clockwise rotations one at a time
+        for _ in 0..self.rotations {
+            let (ox, oy) = (y, size - 1 - x);
+            x = ox;
+            y = oy;
+        }
+        if self.flipped {
+            x = size - 1 - x;
+        }
+        Vector([x, y])
+    }
+}
+
+/// View a square grid in a different orientation.
+fn reorient<T: Clone>(grid: &Grid2D<T>, orientation: Orientation) -> Grid2D<T> {
+    let size = grid.size()[0];
+    Grid2D::from_fn(grid.size(), |p| {
+        grid.get(orientation.source_point(p, size))
+            .cloned()
+            .unwrap()
+    })
+}
+
+const TOP: usize = 0;
+const RIGHT: usize = 1;
+const BOTTOM: usize = 2;
+const LEFT: usize = 3;
+
+struct Tile {
+    id: u64,
+    grid: Grid2D<bool>,
+    /// Edge signatures for every orientation, indexed by `Orientation::index()` and then by side.
+    ///
+    /// Each edge is read left-to-right or top-to-bottom, so touching edges of neighbouring tiles
+    /// match when their signatures are equal.
+    edges: [[Edge; 4]; 8],
+}
+
+impl Tile {
+    fn new(id: u64, grid: Grid2D<bool>) -> crate::Result<Self> {
+        let Vector([w, h]) = grid.size();
+        // Tiles need an inside as well as a border to contribute to the image
+        if w != h || w < 3 || w as u32 > Edge::BITS {
+            return Err(crate::Error::Parse(
+                format!(
+                    "tile {} must be square, and from 3 to {} wide",
+                    id,
+                    Edge::BITS
+                )
+                .into(),
+            ));
+        }
+        let mut edges = [[0; 4]; 8];
+        for orientation in Orientation::all() {
+            let sides = &mut edges[orientation.index()];
+            for i in 0..w {
+                let sample =
+                    |p: Vector2D<i64>| *grid.get(orientation.source_point(p, w)).unwrap() as Edge;
+                sides[TOP] = (sides[TOP] << 1) | sample(Vector([i, 0]));
+                sides[RIGHT] = (sides[RIGHT] << 1) | sample(Vector([w - 1, i]));
+                sides[BOTTOM] = (sides[BOTTOM] << 1) | sample(Vector([i, w - 1]));
+                sides[LEFT] = (sides[LEFT] << 1) | sample(Vector([0, i]));
+            }
+        }
+        Ok(Tile { id, grid, edges })
+    }
+
+    fn edge(&self, orientation: Orientation, side: usize) -> Edge {
+        self.edges[orientation.index()][side]
+    }
+}
+
+/// Tiles assembled into a square, in row-major order, each with the orientation it was placed in.
+struct Arrangement {
+    width: usize,
+    placed: Vec<(usize, Orientation)>,
+}
+
+struct Puzzle {
+    tiles: Vec<Tile>,
+    width: usize,
+    /// Every `(tile, orientation)` indexed by its left edge.
+    by_left: HashMap<Edge, Vec<(usize, Orientation)>>,
+    /// Every `(tile, orientation)` indexed by its top edge.
+    by_top: HashMap<Edge, Vec<(usize, Orientation)>>,
+}
+
+impl Puzzle {
+    fn new(tiles: Vec<Tile>) -> crate::Result<Self> {
+        let width = (tiles.len() as f64).sqrt() as usize;
+        if width * width != tiles.len() || width == 0 {
+            return Err(format!("can't make a square from {} tiles", tiles.len()).into());
+        }
+        if tiles.iter().any(|t| t.grid.size() != tiles[0].grid.size()) {
+            return Err("tiles must all be the same size".into());
+        }
+        let mut by_left: HashMap<Edge, Vec<(usize, Orientation)>> = HashMap::new();
+        let mut by_top: HashMap<Edge, Vec<(usize, Orientation)>> = HashMap::new();
+        for (i, tile) in tiles.iter().enumerate() {
+            for orientation in Orientation::all() {
+                by_left
+                    .entry(tile.edge(orientation, LEFT))
+                    .or_default()
+                    .push((i, orientation));
+                by_top
+                    .entry(tile.edge(orientation, TOP))
+                    .or_default()
+                    .push((i, orientation));
+            }
+        }
+        Ok(Puzzle {
+            tiles,
+            width,
+            by_left,
+            by_top,
+        })
+    }
+
+    /// Assemble the tiles into a square where all touching edges match.
+    ///
+    /// Fills positions in row-major order with a depth-first search, backtracking when no unused
+    /// tile fits. Candidates are looked up by the edge they need to match rather than by trying
+    /// every tile in every orientation, so in practice there is very little backtracking.
+    fn assemble(&self) -> crate::Result<Arrangement> {
+        let mut placed = Vec::with_capacity(self.tiles.len());
+        let mut used = vec![false; self.tiles.len()];
+        if self.place_next(&mut placed, &mut used) {
+            Ok(Arrangement {
+                width: self.width,
+                placed,
+            })
+        } else {
+            Err("no arrangement of tiles found".into())
+        }
+    }
+
+    fn place_next(&self, placed: &mut Vec<(usize, Orientation)>, used: &mut [bool]) -> bool {
+        let pos = placed.len();
+        if pos == self.tiles.len() {
+            return true;
+        }
+        let (row, col) = (pos / self.width, pos % self.width);
+        let above = if row > 0 {
+            let (t, o) = placed[pos - self.width];
+            Some(self.tiles[t].edge(o, BOTTOM))
+        } else {
+            None
+        };
+        let left = if col > 0 {
+            let (t, o) = placed[pos - 1];
+            Some(self.tiles[t].edge(o, RIGHT))
+        } else {
+            None
+        };
+
+        let candidates: Vec<(usize, Orientation)> = match (left, above) {
+            (Some(edge), _) => self.by_left.get(&edge).cloned().unwrap_or_default(),
+            (None, Some(edge)) => self.by_top.get(&edge).cloned().unwrap_or_default(),
+            (None, None) => (0..self.tiles.len())
+                .flat_map(|t| Orientation::all().map(move |o| (t, o)))
+                .collect(),
+        };
+
+        for (t, o) in candidates {
+            if used[t] || above.is_some_and(|edge| self.tiles[t].edge(o, TOP) != edge) {
+                continue;
+            }
+            used[t] = true;
+            placed.push((t, o));
+            if self.place_next(placed, used) {
+                return true;
+            }
+            placed.pop();
+            used[t] = false;
+        }
+        false
+    }
+
+    fn corner_ids(&self, arrangement: &Arrangement) -> [u64; 4] {
+        let w = arrangement.width;
+        let id = |pos: usize| self.tiles[arrangement.placed[pos].0].id;
+        [id(0), id(w - 1), id(w * (w - 1)), id(w * w - 1)]
+    }
+
+    /// Combine the arranged tiles into a single image, with the border of each tile removed.
+    fn image(&self, arrangement: &Arrangement) -> Grid2D<bool> {
+        let tile_size = self.tiles[0].grid.size()[0];
+        let inner = tile_size - 2;
+        let oriented: Vec<Grid2D<bool>> = arrangement
+            .placed
+            .iter()
+            .map(|&(t, o)| reorient(&self.tiles[t].grid, o))
+            .collect();
+        let size = inner * arrangement.width as i64;
+        Grid2D::from_fn(Vector([size, size]), |Vector([x, y])| {
+            let pos = (y / inner) as usize * arrangement.width + (x / inner) as usize;
+            *oriented[pos]
+                .get(Vector([x % inner + 1, y % inner + 1]))
+                .unwrap()
+        })
+    }
+}
+
+const SEA_MONSTER: &[&str] = &[
+    "                  # ",
+    "#    ##    ##    ###",
+    " #  #  #  #  #  #   ",
+];
+
+fn sea_monster_offsets() -> Vec<Vector2D<i64>> {
+    SEA_MONSTER
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.bytes()
+                .enumerate()
+                .filter(|&(_, b)| b == b'#')
+                .map(move |(x, _)| Vector([x as i64, y as i64]))
+        })
+        .collect()
+}
+
+/// Find every cell that is part of a sea monster in `image`, in its current orientation.
+fn find_sea_monsters(image: &Grid2D<bool>) -> HashSet<Vector2D<i64>> {
+    let offsets = sea_monster_offsets();
+    let mut found = HashSet::new();
+    for origin in image.iter_points() {
+        if offsets
+            .iter()
+            .all(|&offset| image.get(origin + offset) == Some(&true))
+        {
+            found.extend(offsets.iter().map(|&offset| origin + offset));
+        }
+    }
+    found
+}
+
+fn read_input(input_path: &PathBuf) -> crate::Result<Vec<Tile>> {
+    let file = File::open(input_path)?;
+    let mut tiles = Vec::new();
+    let mut id: Option<u64> = None;
+    let mut rows: Vec<Vec<bool>> = Vec::new();
+
+    fn finalise(
+        id: &mut Option<u64>,
+        rows: &mut Vec<Vec<bool>>,
+        tiles: &mut Vec<Tile>,
+    ) -> crate::Result<()> {
+        if let Some(id) = id.take() {
+            tiles.push(Tile::new(id, Grid2D::from_rows(rows.split_off(0))?)?);
+        }
+        Ok(())
+    }
+
+    for line in io::BufReader::new(file).lines() {
+        let line = line?;
+        if line.is_empty() {
+            finalise(&mut id, &mut rows, &mut tiles)?;
+        } else if line.starts_with("Tile ") && line.ends_with(':') {
+            finalise(&mut id, &mut rows, &mut tiles)?;
+            id = Some(line[5..(line.len() - 1)].parse()?);
+        } else if id.is_some() {
+            let row = line
+                .bytes()
+                .map(|b| match b {
+                    b'#' => Ok(true),
+                    b'.' => Ok(false),
+                    _ => Err(crate::Error::from(format!(
+                        "unexpected byte in tile: {}",
+                        b
+                    ))),
+                })
+                .collect::<crate::Result<Vec<_>>>()?;
+            rows.push(row);
+        } else {
+            return Err(format!("expected tile header, got {:?}", line).into());
+        }
+    }
+    finalise(&mut id, &mut rows, &mut tiles)?;
+
+    Ok(tiles)
+}
+
+/// Part 1: "What do you get if you multiply together the IDs of the four corner tiles?"
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    let puzzle = Puzzle::new(read_input(&input_path)?)?;
+    let arrangement = puzzle.assemble()?;
+    Ok(puzzle
+        .corner_ids(&arrangement)
+        .iter()
+        .product::<u64>()
+        .to_string())
+}
+
+/// Part 2: "How many `#` are not part of a sea monster?"
+///
+/// Search for sea monsters in every orientation of the assembled image, and use the orientation
+/// where they were found.
+fn part2(input_path: PathBuf) -> crate::Result<String> {
+    let puzzle = Puzzle::new(read_input(&input_path)?)?;
+    let arrangement = puzzle.assemble()?;
+    let image = puzzle.image(&arrangement);
+    let monster_cells = Orientation::all()
+        .map(|o| find_sea_monsters(&reorient(&image, o)))
+        .find(|cells| !cells.is_empty())
+        .ok_or("no sea monsters found")?;
+    let rough = image.iter_cells().filter(|(_, &v)| v).count() - monster_cells.len();
+    Ok(rough.to_string())
+}
+
+pub fn register(runner: &mut crate::Runner) {
+    runner.add("day20part1", || part1(data_path!("day20_input.txt")));
+    runner.add("day20part2", || part2(data_path!("day20_input.txt")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: Orientation = Orientation {
+        flipped: false,
+        rotations: 0,
+    };
+
+    #[test]
+    fn test_orientations_distinct() {
+        let grid = Grid2D::from_rows(vec![vec![1, 2], vec![3, 4]]).unwrap();
+        let mut seen: Vec<Vec<i32>> = Vec::new();
+        for o in Orientation::all() {
+            let cells: Vec<i32> = reorient(&grid, o).iter_cells().map(|(_, &v)| v).collect();
+            assert!(!seen.contains(&cells));
+            seen.push(cells);
+        }
+        assert_eq!(
+            reorient(&grid, IDENTITY)
+                .iter_cells()
+                .map(|(_, &v)| v)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_tile_edges() {
+        let tiles = read_input(&data_path!("day20_example1.txt")).unwrap();
+        let tile = &tiles[0];
+        assert_eq!(tile.id, 2311);
+        // Top edge is "..##.#..#." in the original orientation
+        assert_eq!(tile.edge(IDENTITY, TOP), 0b0011010010);
+        // Cached signatures match the edges of the reoriented grid
+        for o in Orientation::all() {
+            let rotated = reorient(&tile.grid, o);
+            assert_eq!(
+                Tile::new(0, rotated).unwrap().edges[0],
+                tile.edges[o.index()]
+            );
+        }
+    }
+
+    #[test]
+    fn test_tile_size_limits() {
+        let tile = |rows: &[&str]| {
+            let rows = rows
+                .iter()
+                .map(|row| row.bytes().map(|b| b == b'#').collect())
+                .collect();
+            Tile::new(1, Grid2D::from_rows(rows).unwrap())
+        };
+        assert!(matches!(tile(&["#."]), Err(crate::Error::Parse(_))));
+        assert!(matches!(tile(&["#.", ".#"]), Err(crate::Error::Parse(_))));
+        assert!(matches!(
+            tile(&["#.#", ".#.", "#.#", "..."]),
+            Err(crate::Error::Parse(_))
+        ));
+        assert!(tile(&["#.#", ".#.", "#.#"]).is_ok());
+    }
+
+    #[test]
+    fn test_part1_example1() {
+        assert_eq!(
+            part1(data_path!("day20_example1.txt")).unwrap(),
+            "20899048083289"
+        );
+    }
+
+    #[test]
+    fn test_part2_example1() {
+        assert_eq!(part2(data_path!("day20_example1.txt")).unwrap(), "273");
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+use super::prelude::*;
+use crate::util;
+
+struct Food {
+    ingredients: HashSet<String>,
+    allergens: HashSet<String>,
+}
+
+impl FromStr for Food {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (raw_ingredients, raw_allergens) = util::str_partition(s, " (contains ");
+        let ingredients: HashSet<String> = raw_ingredients
+            .split_whitespace()
+            .map(|i| i.to_owned())
+            .collect();
+        if ingredients.is_empty() {
+            return Err(format!("no ingredients in food: {}", s).into());
+        }
+        let allergens = if raw_allergens.is_empty() {
+            HashSet::new()
+        } else if let Some(raw_allergens) = raw_allergens.strip_suffix(')') {
+            raw_allergens.split(", ").map(|a| a.to_owned()).collect()
+        } else {
+            return Err(format!("unterminated allergen list in food: {}", s).into());
+        };
+        Ok(Food {
+            ingredients,
+            allergens,
+        })
+    }
+}
+
+/// For each allergen, find the ingredients that could contain it.
+///
+/// Each allergen is found in exactly one ingredient, so it must be an ingredient of every food
+/// that lists the allergen, i.e. in the intersection of those foods' ingredients.
+fn allergen_candidates(foods: &[Food]) -> HashMap<&str, HashSet<&str>> {
+    let mut candidates: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for food in foods {
+        let ingredients: HashSet<&str> = food.ingredients.iter().map(|i| i.as_str()).collect();
+        for allergen in food.allergens.iter() {
+            candidates
+                .entry(allergen)
+                .and_modify(|c| c.retain(|i| ingredients.contains(i)))
+                .or_insert_with(|| ingredients.clone());
+        }
+    }
+    candidates
+}
+
+fn read_input(input_path: &PathBuf) -> crate::Result<Vec<Food>> {
+    let file = File::open(input_path)?;
+    io::BufReader::new(file)
+        .lines()
+        .map(|line| line?.parse())
+        .collect()
+}
+
+/// Part 1: "Determine which ingredients cannot possibly contain any of the allergens in your list.
+/// How many times do any of those ingredients appear?"
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    let foods = read_input(&input_path)?;
+    let candidates = allergen_candidates(&foods);
+    let maybe_allergenic: HashSet<&str> = candidates.values().flatten().cloned().collect();
+    let count: usize = foods
+        .iter()
+        .map(|food| {
+            food.ingredients
+                .iter()
+                .filter(|i| !maybe_allergenic.contains(i.as_str()))
+                .count()
+        })
+        .sum();
+    Ok(count.to_string())
+}
+
+/// Part 2: "What is your canonical dangerous ingredient list?"
+///
+/// Resolve which ingredient contains each allergen by elimination, then list the ingredients
+/// sorted alphabetically by their allergen.
+fn part2(input_path: PathBuf) -> crate::Result<String> {
+    let foods = read_input(&input_path)?;
+    let mapping: BTreeMap<&str, &str> = util::resolve_unique_mapping(allergen_candidates(&foods))
+        .map_err(|err| format!("can't identify allergens: {}", err))?
+        .into_iter()
+        .collect();
+    Ok(mapping.values().cloned().collect::<Vec<_>>().join(","))
+}
+
+pub fn register(runner: &mut crate::Runner) {
+    runner.add("day21part1", || part1(data_path!("day21_input.txt")));
+    runner.add("day21part2", || part2(data_path!("day21_input.txt")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_food_from_str() {
+        let food: Food = "mxmxvkd kfcds (contains dairy, fish)".parse().unwrap();
+        assert_eq!(food.ingredients.len(), 2);
+        assert!(food.allergens.contains("dairy"));
+        assert!(food.allergens.contains("fish"));
+        let food: Food = "mxmxvkd".parse().unwrap();
+        assert!(food.allergens.is_empty());
+        assert!("mxmxvkd (contains dairy".parse::<Food>().is_err());
+    }
+
+    #[test]
+    fn test_ambiguous_allergens() {
+        let foods: Vec<Food> = vec!["a b (contains x, y)".parse().unwrap()];
+        let err = util::resolve_unique_mapping(allergen_candidates(&foods)).unwrap_err();
+        assert!(err.to_string().starts_with("mapping is not unique"));
+    }
+
+    #[test]
+    fn test_part1_example1() {
+        assert_eq!(part1(data_path!("day21_example1.txt")).unwrap(), "5");
+    }
+
+    #[test]
+    fn test_part2_example1() {
+        assert_eq!(
+            part2(data_path!("day21_example1.txt")).unwrap(),
+            "mxmxvkd,sqjhc,fvjkl"
+        );
+    }
+}
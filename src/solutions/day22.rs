@@ -1,4 +1,5 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write;
 
 use super::prelude::*;
 use crate::util;
@@ -65,8 +66,146 @@ fn part1(input_path: PathBuf) -> crate::Result<String> {
     Ok(score(&winner).to_string())
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    fn number(&self) -> u8 {
+        match self {
+            Player::One => 1,
+            Player::Two => 2,
+        }
+    }
+}
+
+fn format_deck(deck: &Deck) -> String {
+    deck.iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// State for playing "Recursive Combat", which can spawn any number of sub-games.
+struct RecursiveCombat {
+    /// How many games have been started, used to number each new game.
+    games: usize,
+    /// If enabled, a transcript of every round in the same format as the puzzle's example.
+    trace: Option<String>,
+}
+
+impl RecursiveCombat {
+    fn new(trace: bool) -> Self {
+        RecursiveCombat {
+            games: 0,
+            trace: if trace { Some(String::new()) } else { None },
+        }
+    }
+
+    fn trace(&mut self, args: std::fmt::Arguments) {
+        if let Some(trace) = self.trace.as_mut() {
+            // Writing to a `String` can't fail
+            trace.write_fmt(args).unwrap();
+        }
+    }
+
+    /// Play a game of "Recursive Combat" to completion, returning the winner.
+    ///
+    /// The infinite game rule is enforced with a set of every `(Deck, Deck)` state seen at the start
+    /// of a round in this game.
+    fn play_game(&mut self, p1: &mut Deck, p2: &mut Deck) -> Player {
+        self.games += 1;
+        let game = self.games;
+        self.trace(format_args!("=== Game {} ===\n", game));
+
+        let mut seen: HashSet<(Deck, Deck)> = HashSet::new();
+        let mut round = 0;
+        let winner = loop {
+            if p1.is_empty() {
+                break Player::Two;
+            } else if p2.is_empty() || !seen.insert((p1.clone(), p2.clone())) {
+                // Repeating a previous state in this game means player 1 wins
+                break Player::One;
+            }
+
+            round += 1;
+            self.trace(format_args!(
+                "\n-- Round {} (Game {}) --\nPlayer 1's deck: {}\nPlayer 2's deck: {}\n",
+                round,
+                game,
+                format_deck(p1),
+                format_deck(p2)
+            ));
+
+            let c1 = p1.pop_front().unwrap();
+            let c2 = p2.pop_front().unwrap();
+            self.trace(format_args!(
+                "Player 1 plays: {}\nPlayer 2 plays: {}\n",
+                c1, c2
+            ));
+
+            let round_winner = if p1.len() >= c1 as usize && p2.len() >= c2 as usize {
+                self.trace(format_args!(
+                    "Playing a sub-game to determine the winner...\n\n"
+                ));
+                let mut sub_p1: Deck = p1.iter().take(c1 as usize).cloned().collect();
+                let mut sub_p2: Deck = p2.iter().take(c2 as usize).cloned().collect();
+                // Player 1 can't lose a sub-game while holding its highest card: that card can never
+                // be taken from them, so the sub-game ends either with player 2 running out of cards
+                // or by the infinite game rule, and both are a win for player 1. Only take the
+                // shortcut when not tracing, so the transcript still includes every sub-game.
+                let sub_winner =
+                    if self.trace.is_none() && sub_p1.iter().max() > sub_p2.iter().max() {
+                        Player::One
+                    } else {
+                        self.play_game(&mut sub_p1, &mut sub_p2)
+                    };
+                self.trace(format_args!("\n...anyway, back to game {}.\n", game));
+                sub_winner
+            } else if c1 > c2 {
+                Player::One
+            } else {
+                Player::Two
+            };
+            self.trace(format_args!(
+                "Player {} wins round {} of game {}!\n",
+                round_winner.number(),
+                round,
+                game
+            ));
+
+            match round_winner {
+                Player::One => {
+                    p1.push_back(c1);
+                    p1.push_back(c2);
+                }
+                Player::Two => {
+                    p2.push_back(c2);
+                    p2.push_back(c1);
+                }
+            }
+        };
+
+        self.trace(format_args!(
+            "The winner of game {} is player {}!\n",
+            game,
+            winner.number()
+        ));
+        winner
+    }
+}
+
 fn part2(input_path: PathBuf) -> crate::Result<String> {
-    Err("unimplemented".into())
+    let (mut p1, mut p2) = read_input(&input_path)?;
+
+    let winner = match RecursiveCombat::new(false).play_game(&mut p1, &mut p2) {
+        Player::One => p1,
+        Player::Two => p2,
+    };
+
+    Ok(score(&winner).to_string())
 }
 
 pub fn register(runner: &mut crate::Runner) {
@@ -88,8 +227,76 @@ mod tests {
         assert_eq!(part1(data_path!("day22_input.txt")).unwrap(), "32598");
     }
 
+    #[test]
+    fn test_recursive_combat_trace_example1() {
+        let (mut p1, mut p2) = read_input(&data_path!("day22_example1.txt")).unwrap();
+        let mut game = RecursiveCombat::new(true);
+        assert_eq!(game.play_game(&mut p1, &mut p2), Player::Two);
+        let trace = game.trace.unwrap();
+        assert!(trace.starts_with(
+            "=== Game 1 ===
+
+-- Round 1 (Game 1) --
+Player 1's deck: 9, 2, 6, 3, 1
+Player 2's deck: 5, 8, 4, 7, 10
+Player 1 plays: 9
+Player 2 plays: 5
+Player 1 wins round 1 of game 1!
+
+-- Round 2 (Game 1) --
+Player 1's deck: 2, 6, 3, 1, 9, 5
+Player 2's deck: 8, 4, 7, 10
+Player 1 plays: 2
+Player 2 plays: 8
+Player 2 wins round 2 of game 1!
+"
+        ));
+        assert!(trace.contains(
+            "-- Round 9 (Game 1) --
+Player 1's deck: 4, 9, 8, 5, 2
+Player 2's deck: 3, 10, 1, 7, 6
+Player 1 plays: 4
+Player 2 plays: 3
+Playing a sub-game to determine the winner...
+
+=== Game 2 ===
+
+-- Round 1 (Game 2) --
+Player 1's deck: 9, 8, 5, 2
+Player 2's deck: 10, 1, 7
+Player 1 plays: 9
+Player 2 plays: 10
+Player 2 wins round 1 of game 2!
+"
+        ));
+        assert!(trace.contains(
+            "The winner of game 2 is player 2!
+
+...anyway, back to game 1.
+Player 2 wins round 9 of game 1!
+"
+        ));
+        assert!(trace.ends_with("The winner of game 1 is player 2!\n"));
+        assert_eq!(score(&p2), 291);
+    }
+
+    #[test]
+    fn test_recursive_combat_infinite_game() {
+        let mut p1: Deck = vec![43, 19].into_iter().collect();
+        let mut p2: Deck = vec![2, 29, 14].into_iter().collect();
+        assert_eq!(
+            RecursiveCombat::new(false).play_game(&mut p1, &mut p2),
+            Player::One
+        );
+    }
+
+    #[test]
+    fn test_part2_example1() {
+        assert_eq!(part2(data_path!("day22_example1.txt")).unwrap(), "291");
+    }
+
     #[test]
     fn test_part2_solution() {
-        assert_eq!(part2(data_path!("day22_input.txt")).unwrap(), "");
+        assert_eq!(part2(data_path!("day22_input.txt")).unwrap(), "35836");
     }
 }
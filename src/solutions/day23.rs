@@ -0,0 +1,127 @@
+use super::prelude::*;
+use crate::util::CircularList;
+
+fn read_input(input_path: &PathBuf) -> crate::Result<Vec<u32>> {
+    let mut line = String::new();
+    io::BufReader::new(File::open(input_path)?).read_line(&mut line)?;
+    line.trim()
+        .chars()
+        .map(|c| {
+            c.to_digit(10)
+                .filter(|&d| d > 0)
+                .ok_or_else(|| format!("invalid cup label: {:?}", c).into())
+        })
+        .collect()
+}
+
+/// Cups arranged in a circle, stored as a successor array indexed by cup label so that finding the
+/// destination cup and moving the picked up cups are both O(1).
+struct Cups {
+    ring: CircularList,
+    current: u32,
+    max: u32,
+}
+
+impl Cups {
+    /// Arrange cups labelled with `labels`, followed by the rest of the labels up to `total`.
+    fn new(labels: &[u32], total: u32) -> crate::Result<Self> {
+        // Labels are distinct (checked by `CircularList`), so this means they are exactly 1 to N
+        let max_label = labels.iter().cloned().max().unwrap_or(0);
+        if labels.len() < 5 || labels.contains(&0) || labels.len() as u32 != max_label {
+            return Err("cups must be labelled 1 to N, with at least 5 cups".into());
+        }
+        let mut all = labels.to_vec();
+        all.extend((max_label + 1)..=total);
+        Ok(Cups {
+            ring: CircularList::new(&all)?,
+            current: all[0],
+            max: all.len() as u32,
+        })
+    }
+
+    /// Perform one move of the crab's game.
+    fn step(&mut self) {
+        let first = self.ring.next(self.current);
+        let second = self.ring.next(first);
+        let third = self.ring.next(second);
+
+        let mut dest = self.current;
+        loop {
+            dest = if dest == 1 { self.max } else { dest - 1 };
+            if dest != first && dest != second && dest != third {
+                break;
+            }
+        }
+
+        self.ring.splice_after(self.current, third, dest);
+        self.current = self.ring.next(self.current);
+    }
+
+    fn run(&mut self, moves: usize) {
+        for _ in 0..moves {
+            self.step();
+        }
+    }
+
+    /// Iterate over labels after cup 1, not including cup 1 itself.
+    fn iter_after_one(&self) -> impl Iterator<Item = u32> + '_ {
+        self.ring.iter_from(1).skip(1)
+    }
+}
+
+fn part1_impl(labels: &[u32], moves: usize) -> crate::Result<String> {
+    let mut cups = Cups::new(labels, labels.len() as u32)?;
+    cups.run(moves);
+    Ok(cups.iter_after_one().map(|c| c.to_string()).collect())
+}
+
+/// Part 1: "Using your labeling, simulate 100 moves. What are the labels on the cups after cup 1?"
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    part1_impl(&read_input(&input_path)?, 100)
+}
+
+/// Run part 2 of the game: one million cups, ten million moves, returning the product of the two
+/// labels after cup 1.
+pub fn part2_impl(labels: &[u32]) -> crate::Result<u64> {
+    let mut cups = Cups::new(labels, 1_000_000)?;
+    cups.run(10_000_000);
+    Ok(cups.iter_after_one().take(2).map(|c| c as u64).product())
+}
+
+/// Part 2: "Determine which two cups will end up immediately clockwise of cup 1. What do you get if
+/// you multiply their labels together?"
+fn part2(input_path: PathBuf) -> crate::Result<String> {
+    part2_impl(&read_input(&input_path)?).map(|x| x.to_string())
+}
+
+pub fn register(runner: &mut crate::Runner) {
+    runner.add("day23part1", || part1(data_path!("day23_input.txt")));
+    runner.add("day23part2", || part2(data_path!("day23_input.txt")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part1_impl_example1() {
+        let labels = read_input(&data_path!("day23_example1.txt")).unwrap();
+        assert_eq!(part1_impl(&labels, 10).unwrap(), "92658374");
+        assert_eq!(part1_impl(&labels, 100).unwrap(), "67384529");
+    }
+
+    #[test]
+    fn test_cups_invalid() {
+        assert!(Cups::new(&[2, 3, 4, 5, 6], 5).is_err());
+        assert!(Cups::new(&[1, 2, 4, 5, 6], 5).is_err());
+        assert!(Cups::new(&[1, 2, 3], 3).is_err());
+    }
+
+    #[test]
+    fn test_part2_example1() {
+        assert_eq!(
+            part2(data_path!("day23_example1.txt")).unwrap(),
+            "149245887792"
+        );
+    }
+}
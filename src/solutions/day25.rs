@@ -0,0 +1,56 @@
+use super::prelude::*;
+use crate::modular;
+
+const MODULUS: u64 = 20201227;
+const SUBJECT_NUMBER: u64 = 7;
+
+fn read_input(input_path: &PathBuf) -> crate::Result<(u64, u64)> {
+    let file = File::open(input_path)?;
+    let keys = io::BufReader::new(file)
+        .lines()
+        .map(|line| Ok(line?.trim().parse::<u64>()?))
+        .collect::<crate::Result<Vec<_>>>()?;
+    match keys[..] {
+        [card, door] => Ok((card, door)),
+        _ => Err(format!("expected 2 public keys, got {}", keys.len()).into()),
+    }
+}
+
+/// Find the loop size that transforms the subject number into `public_key`.
+///
+/// Transforming is modular exponentiation, so finding the loop size is a discrete logarithm.
+fn find_loop_size(public_key: u64) -> crate::Result<u64> {
+    modular::discrete_log(SUBJECT_NUMBER, public_key, MODULUS)
+        .ok_or_else(|| format!("no loop size produces public key {}", public_key).into())
+}
+
+fn encryption_key(card_public_key: u64, door_public_key: u64) -> crate::Result<u64> {
+    let card_loop_size = find_loop_size(card_public_key)?;
+    Ok(modular::mod_pow(door_public_key, card_loop_size, MODULUS))
+}
+
+/// Part 1: "What encryption key is the handshake trying to establish?"
+fn part1(input_path: PathBuf) -> crate::Result<String> {
+    let (card, door) = read_input(&input_path)?;
+    encryption_key(card, door).map(|key| key.to_string())
+}
+
+pub fn register(runner: &mut crate::Runner) {
+    runner.add("day25part1", || part1(data_path!("day25_input.txt")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_loop_size() {
+        assert_eq!(find_loop_size(5764801).unwrap(), 8);
+        assert_eq!(find_loop_size(17807724).unwrap(), 11);
+    }
+
+    #[test]
+    fn test_part1_example1() {
+        assert_eq!(part1(data_path!("day25_example1.txt")).unwrap(), "14897079");
+    }
+}
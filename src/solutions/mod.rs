@@ -16,13 +16,13 @@ pub mod day15;
 pub mod day16;
 pub mod day17;
 pub mod day18;
-// pub mod day19;
-// pub mod day20;
-// pub mod day21;
+pub mod day19;
+pub mod day20;
+pub mod day21;
 pub mod day22;
-// pub mod day23;
+pub mod day23;
 pub mod day24;
-// pub mod day25;
+pub mod day25;
 
 mod prelude;
 
@@ -47,13 +47,13 @@ pub fn build_runner() -> crate::Runner {
     day16::register(&mut runner);
     day17::register(&mut runner);
     day18::register(&mut runner);
-    // day19::register(&mut runner);
-    // day20::register(&mut runner);
-    // day21::register(&mut runner);
+    day19::register(&mut runner);
+    day20::register(&mut runner);
+    day21::register(&mut runner);
     day22::register(&mut runner);
-    // day23::register(&mut runner);
+    day23::register(&mut runner);
     day24::register(&mut runner);
-    // day25::register(&mut runner);
+    day25::register(&mut runner);
 
     runner
 }
@@ -1,5 +1,7 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::File;
+use std::hash::Hash;
 use std::io::{self, BufRead};
 use std::path::PathBuf;
 
@@ -46,6 +48,112 @@ pub fn str_rpartition<'a>(input: &'a str, sep: &str) -> (&'a str, &'a str) {
     }
 }
 
+/// Resolve a one-to-one mapping from each key's set of candidate values.
+///
+/// Repeatedly fixes any key that has exactly one candidate left, removing that value from every
+/// other key's candidates, until all keys are resolved. Returns an error if a key runs out of
+/// candidates, or if the constraints don't determine a unique mapping.
+pub fn resolve_unique_mapping<K, V>(
+    mut candidates: HashMap<K, HashSet<V>>,
+) -> crate::Result<HashMap<K, V>>
+where
+    K: Eq + Hash + Clone + Debug,
+    V: Eq + Hash + Clone + Debug,
+{
+    let mut resolved: HashMap<K, V> = HashMap::new();
+    while !candidates.is_empty() {
+        if let Some((key, _)) = candidates.iter().find(|(_, values)| values.is_empty()) {
+            return Err(format!("no candidates left for {:?}", key).into());
+        }
+        let fixed: Vec<(K, V)> = candidates
+            .iter()
+            .filter(|(_, values)| values.len() == 1)
+            .map(|(key, values)| (key.clone(), values.iter().next().cloned().unwrap()))
+            .collect();
+        if fixed.is_empty() {
+            return Err(format!("mapping is not unique, unresolved: {:?}", candidates).into());
+        }
+        for (key, value) in fixed {
+            // Another key fixed in this pass may have claimed the same value
+            if !candidates
+                .get(&key)
+                .is_some_and(|values| values.contains(&value))
+            {
+                continue;
+            }
+            candidates.remove(&key);
+            for values in candidates.values_mut() {
+                values.remove(&value);
+            }
+            resolved.insert(key, value);
+        }
+    }
+    Ok(resolved)
+}
+
+/// Circular singly-linked list of distinct `u32` labels, stored as an array of successors indexed
+/// by label.
+///
+/// Finding a label's node, and moving a run of nodes elsewhere in the list, are both O(1). Storage
+/// is proportional to the largest label, so labels should be reasonably dense.
+#[derive(Clone, Debug)]
+pub struct CircularList {
+    next: Vec<u32>,
+    len: usize,
+}
+
+impl CircularList {
+    /// Create a list containing `labels`, in order, with the last linked back to the first.
+    pub fn new(labels: &[u32]) -> crate::Result<Self> {
+        let max = *labels.iter().max().ok_or("at least one label required")?;
+        // Labels not in the list point at themselves, which also marks them as unused
+        let mut next: Vec<u32> = (0..=max).collect();
+        let mut seen = vec![false; max as usize + 1];
+        for (i, &label) in labels.iter().enumerate() {
+            if seen[label as usize] {
+                return Err(format!("duplicate label {}", label).into());
+            }
+            seen[label as usize] = true;
+            next[label as usize] = labels[(i + 1) % labels.len()];
+        }
+        Ok(CircularList {
+            next,
+            len: labels.len(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The label following `label`.
+    #[inline]
+    pub fn next(&self, label: u32) -> u32 {
+        self.next[label as usize]
+    }
+
+    /// Move the run of labels that starts after `prev` and ends with `last` so that it follows
+    /// `dest` instead.
+    ///
+    /// `dest` must not be part of the run being moved.
+    #[inline]
+    pub fn splice_after(&mut self, prev: u32, last: u32, dest: u32) {
+        let first = self.next(prev);
+        self.next[prev as usize] = self.next(last);
+        self.next[last as usize] = self.next(dest);
+        self.next[dest as usize] = first;
+    }
+
+    /// Iterate once around the list, starting at `label`.
+    pub fn iter_from(&self, label: u32) -> impl Iterator<Item = u32> + '_ {
+        std::iter::successors(Some(label), move |&l| Some(self.next(l))).take(self.len)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Rect<C: Coord>(pub Vector2D<C>);
 
@@ -66,8 +174,9 @@ impl<C: Coord> Rect<C> {
         (C::zero()..w).contains(&x) && (C::zero()..h).contains(&y)
     }
 
+    /// Wrap the x coordinate into the rectangle, so that e.g. `-1` wraps to `width - 1`.
     pub fn wrap_x(&self, point: Vector2D<C>) -> Vector2D<C> {
-        Vector([point[0] % self.size()[0], point[1]])
+        Vector([point[0].mod_floor(&self.size()[0]), point[1]])
     }
 
     pub fn row_major_index(&self, point: Vector2D<C>) -> Option<usize> {
@@ -143,6 +252,21 @@ impl<T> Grid2D<T> {
         })
     }
 
+    /// Build a grid of `size` by calling `f` for every point, in row-major order.
+    pub fn from_fn<F: FnMut(Vector2D<i64>) -> T>(size: Vector2D<i64>, f: F) -> Self {
+        let extent = Rect(size);
+        let data = extent.iter_points().map(f).collect();
+        Self { extent, data }
+    }
+
+    pub fn size(&self) -> Vector2D<i64> {
+        *self.extent.size()
+    }
+
+    pub fn extent(&self) -> &Rect<i64> {
+        &self.extent
+    }
+
     pub fn get(&self, point: Vector2D<i64>) -> Option<&T> {
         self.extent
             .row_major_index(point)
@@ -202,6 +326,66 @@ mod tests {
         assert_eq!(str_partition("a,b, c, d", " , "), ("a,b, c, d", ""));
     }
 
+    #[test]
+    fn test_resolve_unique_mapping() {
+        let candidates: HashMap<&str, HashSet<u8>> = vec![
+            ("a", vec![1, 2, 3].into_iter().collect()),
+            ("b", vec![2].into_iter().collect()),
+            ("c", vec![2, 3].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        let expected: HashMap<&str, u8> = vec![("a", 1), ("b", 2), ("c", 3)].into_iter().collect();
+        assert_eq!(resolve_unique_mapping(candidates).unwrap(), expected);
+
+        let ambiguous: HashMap<&str, HashSet<u8>> = vec![
+            ("a", vec![1, 2].into_iter().collect()),
+            ("b", vec![1, 2].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        assert!(resolve_unique_mapping(ambiguous).is_err());
+
+        let contradictory: HashMap<&str, HashSet<u8>> = vec![
+            ("a", vec![1].into_iter().collect()),
+            ("b", vec![1].into_iter().collect()),
+        ]
+        .into_iter()
+        .collect();
+        assert!(resolve_unique_mapping(contradictory).is_err());
+    }
+
+    #[test]
+    fn test_circular_list() {
+        let mut list = CircularList::new(&[3, 8, 9, 1, 2, 5, 4, 6, 7]).unwrap();
+        assert_eq!(list.len(), 9);
+        assert_eq!(list.next(7), 3);
+        // Move "8, 9, 1" to after 2
+        list.splice_after(3, 1, 2);
+        assert_eq!(
+            list.iter_from(3).collect::<Vec<_>>(),
+            vec![3, 2, 8, 9, 1, 5, 4, 6, 7]
+        );
+        // Move "7" to after 3, wrapping around the end
+        list.splice_after(6, 7, 3);
+        assert_eq!(
+            list.iter_from(3).collect::<Vec<_>>(),
+            vec![3, 7, 2, 8, 9, 1, 5, 4, 6]
+        );
+        assert!(CircularList::new(&[1, 2, 1]).is_err());
+        assert!(CircularList::new(&[]).is_err());
+    }
+
+    #[test]
+    fn test_rect_wrap_x() {
+        let rect = Rect(Vector([5_i64, 3]));
+        assert_eq!(rect.wrap_x(Vector([7, 1])), Vector([2, 1]));
+        assert_eq!(rect.wrap_x(Vector([-1, 1])), Vector([4, 1]));
+        assert_eq!(rect.wrap_x(Vector([-10, 2])), Vector([0, 2]));
+        let rect = Rect(Vector([5_usize, 3]));
+        assert_eq!(rect.wrap_x(Vector([12, 0])), Vector([2, 0]));
+    }
+
     #[test]
     fn test_str_rpartition() {
         assert_eq!(str_rpartition("a, b, c, d", ", "), ("a, b, c", "d"));
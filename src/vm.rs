@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+pub type Word = i64;
+
+/// Register file where each register is identified by name, and registers that have never been
+/// written read as 0.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Registers(BTreeMap<&'static str, Word>);
+
+impl Registers {
+    pub fn new() -> Self {
+        Registers(BTreeMap::new())
+    }
+
+    pub fn get(&self, name: &str) -> Word {
+        self.0.get(name).cloned().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, name: &'static str, value: Word) {
+        self.0.insert(name, value);
+    }
+
+    pub fn add(&mut self, name: &'static str, value: Word) {
+        *self.0.entry(name).or_insert(0) += value;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, Word)> + '_ {
+        self.0.iter().map(|(&name, &value)| (name, value))
+    }
+}
+
+/// An instruction set for `Machine`.
+///
+/// Implement this for an enum of operations to define a new instruction set.
+pub trait Instruction: Clone + fmt::Debug {
+    /// Execute the instruction, returning the offset from the current instruction to the next one.
+    fn execute(&self, registers: &mut Registers) -> Word;
+}
+
+/// Why a `Machine` stopped running.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Halt {
+    /// The program counter moved to just after the last instruction.
+    Terminated,
+    /// The instruction at `pc` was about to be executed a second time.
+    ///
+    /// Only correct for instruction sets where control flow doesn't depend on register values,
+    /// otherwise revisiting an instruction doesn't mean the machine is stuck.
+    InfiniteLoop { pc: usize },
+    /// The program counter moved somewhere other than an instruction or just after the end.
+    OutOfBounds { pc: Word },
+    /// Stopped before executing the instruction at a breakpoint.
+    Breakpoint { pc: usize },
+}
+
+/// One executed instruction, as recorded in the execution trace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceEntry {
+    pub pc: usize,
+    pub next_pc: Word,
+    /// Register values after the instruction executed.
+    pub registers: Registers,
+}
+
+pub struct Machine<I: Instruction> {
+    program: Vec<I>,
+    pc: Word,
+    registers: Registers,
+    visited: Vec<bool>,
+    breakpoints: HashSet<usize>,
+    trace: Option<Vec<TraceEntry>>,
+}
+
+impl<I: Instruction> Machine<I> {
+    pub fn new(program: Vec<I>) -> Self {
+        let visited = vec![false; program.len()];
+        Machine {
+            program,
+            pc: 0,
+            registers: Registers::new(),
+            visited,
+            breakpoints: HashSet::new(),
+            trace: None,
+        }
+    }
+
+    pub fn program(&self) -> &[I] {
+        &self.program
+    }
+
+    /// Get the program to modify it, e.g. to patch an instruction before calling `reset()`.
+    pub fn program_mut(&mut self) -> &mut [I] {
+        &mut self.program
+    }
+
+    pub fn pc(&self) -> Word {
+        self.pc
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Return to the initial state, keeping the program, breakpoints and whether tracing is enabled.
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.registers = Registers::new();
+        self.visited.iter_mut().for_each(|v| *v = false);
+        if let Some(trace) = self.trace.as_mut() {
+            trace.clear();
+        }
+    }
+
+    pub fn set_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn clear_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.remove(&pc);
+    }
+
+    /// Start recording every executed instruction.
+    pub fn enable_trace(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(Vec::new());
+        }
+    }
+
+    /// Instructions executed since tracing was enabled or the machine was last reset.
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.trace.as_deref().unwrap_or(&[])
+    }
+
+    /// Check whether the machine can execute the instruction at the current program counter.
+    fn check_pc(&self) -> Result<usize, Halt> {
+        if self.pc == self.program.len() as Word {
+            Err(Halt::Terminated)
+        } else if self.pc < 0 || self.pc > self.program.len() as Word {
+            Err(Halt::OutOfBounds { pc: self.pc })
+        } else if self.visited[self.pc as usize] {
+            Err(Halt::InfiniteLoop {
+                pc: self.pc as usize,
+            })
+        } else {
+            Ok(self.pc as usize)
+        }
+    }
+
+    /// Execute a single instruction, ignoring breakpoints, unless the machine has halted.
+    pub fn step(&mut self) -> Option<Halt> {
+        let pc = match self.check_pc() {
+            Ok(pc) => pc,
+            Err(halt) => return Some(halt),
+        };
+        self.visited[pc] = true;
+        let offset = self.program[pc].execute(&mut self.registers);
+        self.pc += offset;
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry {
+                pc,
+                next_pc: self.pc,
+                registers: self.registers.clone(),
+            });
+        }
+        None
+    }
+
+    /// Execute instructions until the machine halts.
+    ///
+    /// The first instruction is always executed, so that calling `run()` again after stopping at a
+    /// breakpoint continues past it.
+    pub fn run(&mut self) -> Halt {
+        if let Some(halt) = self.step() {
+            return halt;
+        }
+        loop {
+            if let Ok(pc) = self.check_pc() {
+                if self.breakpoints.contains(&pc) {
+                    return Halt::Breakpoint { pc };
+                }
+            }
+            if let Some(halt) = self.step() {
+                return halt;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    enum TestOp {
+        Inc(&'static str),
+        Jmp(Word),
+    }
+
+    impl Instruction for TestOp {
+        fn execute(&self, registers: &mut Registers) -> Word {
+            match self {
+                TestOp::Inc(r) => {
+                    registers.add(r, 1);
+                    1
+                }
+                TestOp::Jmp(offset) => *offset,
+            }
+        }
+    }
+
+    #[test]
+    fn test_halt_reasons() {
+        let mut machine = Machine::new(vec![TestOp::Inc("a"), TestOp::Inc("b")]);
+        assert_eq!(machine.run(), Halt::Terminated);
+        assert_eq!(machine.registers().get("a"), 1);
+        assert_eq!(machine.registers().get("b"), 1);
+        assert_eq!(machine.registers().get("c"), 0);
+
+        let mut machine = Machine::new(vec![TestOp::Inc("a"), TestOp::Jmp(-1)]);
+        assert_eq!(machine.run(), Halt::InfiniteLoop { pc: 0 });
+
+        let mut machine = Machine::new(vec![TestOp::Inc("a"), TestOp::Jmp(-2)]);
+        assert_eq!(machine.run(), Halt::OutOfBounds { pc: -1 });
+        // Halting is sticky
+        assert_eq!(machine.step(), Some(Halt::OutOfBounds { pc: -1 }));
+    }
+
+    #[test]
+    fn test_breakpoints_and_reset() {
+        let program = vec![
+            TestOp::Inc("a"),
+            TestOp::Inc("a"),
+            TestOp::Jmp(2),
+            TestOp::Inc("b"),
+            TestOp::Inc("a"),
+        ];
+        let mut machine = Machine::new(program);
+        machine.set_breakpoint(2);
+        machine.set_breakpoint(3);
+        assert_eq!(machine.run(), Halt::Breakpoint { pc: 2 });
+        assert_eq!(machine.registers().get("a"), 2);
+        assert_eq!(machine.run(), Halt::Terminated);
+        assert_eq!(machine.registers().get("a"), 3);
+        assert_eq!(machine.registers().get("b"), 0);
+
+        machine.reset();
+        machine.clear_breakpoint(2);
+        machine.program_mut()[2] = TestOp::Jmp(1);
+        assert_eq!(machine.run(), Halt::Breakpoint { pc: 3 });
+        assert_eq!(machine.run(), Halt::Terminated);
+        assert_eq!(machine.registers().get("b"), 1);
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut machine = Machine::new(vec![TestOp::Inc("a"), TestOp::Jmp(-1)]);
+        machine.enable_trace();
+        assert_eq!(machine.run(), Halt::InfiniteLoop { pc: 0 });
+        let pcs: Vec<(usize, Word)> = machine
+            .trace()
+            .iter()
+            .map(|entry| (entry.pc, entry.next_pc))
+            .collect();
+        assert_eq!(pcs, vec![(0, 1), (1, 0)]);
+        assert_eq!(machine.trace()[0].registers.get("a"), 1);
+        machine.reset();
+        assert!(machine.trace().is_empty());
+    }
+}